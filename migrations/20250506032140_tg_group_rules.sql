@@ -0,0 +1,15 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS tg_group_rules (
+    id SERIAL,
+    chat_id VARCHAR(255) NOT NULL,
+    rule_type SMALLINT NOT NULL DEFAULT 0,
+    token_address VARCHAR(255) DEFAULT NULL,
+    min_amount BIGINT DEFAULT 0,
+    min_days INTEGER DEFAULT 0,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS tg_group_rules_chat_id_idx ON tg_group_rules (chat_id);
@@ -0,0 +1,17 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS tg_group_tiers (
+    id SERIAL,
+    chat_id VARCHAR(255) NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    token_address VARCHAR(255) DEFAULT NULL,
+    min_balance BIGINT DEFAULT 0,
+    permissions SMALLINT NOT NULL DEFAULT 1,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (id)
+);
+
+CREATE INDEX IF NOT EXISTS tg_group_tiers_chat_id_idx ON tg_group_tiers (chat_id);
+
+ALTER TABLE tg_group_joined ADD COLUMN tier_id INTEGER DEFAULT NULL;
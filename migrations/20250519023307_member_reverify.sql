@@ -0,0 +1,7 @@
+-- Add migration script here
+
+ALTER TABLE tg_groups ADD COLUMN grace_hours INTEGER DEFAULT 24;
+ALTER TABLE tg_groups ADD COLUMN lapse_action SMALLINT NOT NULL DEFAULT 0;
+
+ALTER TABLE tg_group_joined ADD COLUMN checked_at TIMESTAMP DEFAULT NULL;
+ALTER TABLE tg_group_joined ADD COLUMN warned_at TIMESTAMP DEFAULT NULL;
@@ -0,0 +1,6 @@
+-- Add migration script here
+
+ALTER TABLE tg_groups ADD COLUMN kyc_minutes INTEGER DEFAULT 5;
+ALTER TABLE tg_groups ADD COLUMN ban_minutes INTEGER DEFAULT 15;
+
+ALTER TABLE tg_group_joined ADD COLUMN fail_count INTEGER NOT NULL DEFAULT 0;
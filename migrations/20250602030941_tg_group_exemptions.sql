@@ -0,0 +1,10 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS tg_group_exemptions (
+    chat_id VARCHAR(255),
+    kind SMALLINT NOT NULL DEFAULT 0,
+    value VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (chat_id, kind, value)
+);
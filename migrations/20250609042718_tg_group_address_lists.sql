@@ -0,0 +1,10 @@
+-- Add migration script here
+
+ALTER TABLE tg_group_rules ADD COLUMN merkle_root VARCHAR(255) DEFAULT NULL;
+
+CREATE TABLE IF NOT EXISTS tg_group_address_lists (
+    rule_id INTEGER REFERENCES tg_group_rules (id) ON DELETE CASCADE,
+    address VARCHAR(255),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (rule_id, address)
+);
@@ -0,0 +1,5 @@
+-- Add migration script here
+
+ALTER TABLE tg_group_rules ADD COLUMN chain_id BIGINT DEFAULT NULL;
+
+ALTER TABLE tg_group_joined ADD COLUMN evm_address VARCHAR(255) DEFAULT NULL;
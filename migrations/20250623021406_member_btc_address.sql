@@ -0,0 +1,3 @@
+-- Add migration script here
+
+ALTER TABLE tg_group_joined ADD COLUMN btc_address VARCHAR(255) DEFAULT NULL;
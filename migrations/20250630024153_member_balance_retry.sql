@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE tg_group_joined ADD COLUMN balance_retries INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE tg_group_joined ADD COLUMN retry_at TIMESTAMP DEFAULT NULL;
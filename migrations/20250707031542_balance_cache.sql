@@ -0,0 +1,7 @@
+-- Add migration script here
+
+CREATE TABLE balance_cache (
+    lock_hash VARCHAR PRIMARY KEY,
+    balances TEXT NOT NULL,
+    fetched_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
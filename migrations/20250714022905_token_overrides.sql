@@ -0,0 +1,6 @@
+-- Add migration script here
+
+ALTER TABLE tokens ADD COLUMN overridden TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE tokens ADD COLUMN refreshed_at TIMESTAMP DEFAULT NULL;
+
+CREATE INDEX IF NOT EXISTS tokens_refreshed_at_idx ON tokens (refreshed_at);
@@ -0,0 +1,4 @@
+-- Add migration script here
+
+ALTER TABLE tg_groups ADD COLUMN join_mode SMALLINT NOT NULL DEFAULT 0;
+ALTER TABLE tg_group_joined ADD COLUMN via_request BOOLEAN NOT NULL DEFAULT FALSE;
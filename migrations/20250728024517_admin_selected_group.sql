@@ -0,0 +1,3 @@
+-- Add migration script here
+
+ALTER TABLE tg_group_admins ADD COLUMN selected_at TIMESTAMP;
@@ -0,0 +1,8 @@
+-- Add migration script here
+
+CREATE TABLE IF NOT EXISTS tg_group_address_leaves (
+    rule_id INTEGER REFERENCES tg_group_rules (id) ON DELETE CASCADE,
+    leaf VARCHAR(66),
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (rule_id, leaf)
+);
@@ -1,4 +1,4 @@
-use crate::handlers::{member, welcome};
+use crate::handlers::{group, member, metrics, token, welcome};
 use crate::{
     config,
     repositories::{
@@ -13,6 +13,9 @@ use actix_web::{middleware, web, App, HttpServer};
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     welcome::route(cfg);
     member::route(cfg);
+    group::route(cfg);
+    metrics::route(cfg);
+    token::route(cfg);
 }
 
 pub async fn create_app() -> std::io::Result<()> {
@@ -26,9 +29,22 @@ pub async fn create_app() -> std::io::Result<()> {
     }
     let member_dao = repositories::member::MemberDao::new(db.clone());
     let tele_dao = repositories::telegram::TelegramDao::new(db.clone());
+    let rule_dao = repositories::rule::RuleDao::new(db.clone());
+    let rule_service = services::rule::RuleSrv::new(rule_dao.clone());
+    let tier_dao = repositories::tier::TierDao::new(db.clone());
+    let tier_service = services::tier::TierSrv::new(tier_dao.clone());
+    let exemption_dao = repositories::exemption::ExemptionDao::new(db.clone());
     let member_service = web::Data::new(services::member::MemberSrv::new(
         member_dao.clone(),
         tele_dao.clone(),
+        rule_service.clone(),
+        tier_service.clone(),
+        exemption_dao.clone(),
+    ));
+    let rule_service = web::Data::new(rule_service);
+    let token_service = web::Data::new(services::token::TokenSrv::new(
+        repositories::token::TokenDao::new(db.clone()),
+        tele_dao.clone(),
     ));
 
     let listen_address: String = config::get("listen_address");
@@ -44,6 +60,8 @@ pub async fn create_app() -> std::io::Result<()> {
 
         App::new()
             .app_data(member_service.clone())
+            .app_data(rule_service.clone())
+            .app_data(token_service.clone())
             .wrap(cors)
             .wrap(middleware::Logger::default())
             .configure(init_routes)
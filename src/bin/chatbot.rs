@@ -1,7 +1,10 @@
 use std::sync::Arc;
 use utxo_global_tgbot_api::repositories;
 use utxo_global_tgbot_api::repositories::db::{migrate_db, DB_POOL};
+use utxo_global_tgbot_api::services::rule::RuleSrv;
 use utxo_global_tgbot_api::services::telegram::TelegramService;
+use utxo_global_tgbot_api::services::tier::TierSrv;
+use utxo_global_tgbot_api::services::token::TokenSrv;
 
 #[tokio::main]
 async fn main() {
@@ -10,7 +13,13 @@ async fn main() {
     let member_dao = Arc::new(repositories::member::MemberDao::new(db.clone()));
     let tele_dao = Arc::new(repositories::telegram::TelegramDao::new(db.clone()));
 
-    let token_dao = Arc::new(repositories::token::TokenDao::new(db.clone()));
+    let token_srv = Arc::new(TokenSrv::new(
+        repositories::token::TokenDao::new(db.clone()),
+        repositories::telegram::TelegramDao::new(db.clone()),
+    ));
+    let rule_srv = Arc::new(RuleSrv::new(repositories::rule::RuleDao::new(db.clone())));
+    let tier_srv = Arc::new(TierSrv::new(repositories::tier::TierDao::new(db.clone())));
+    let exemption_dao = Arc::new(repositories::exemption::ExemptionDao::new(db.clone()));
 
     // migrate db
     if let Err(e) = migrate_db().await {
@@ -21,7 +30,10 @@ async fn main() {
     let telegram_srv = Arc::new(TelegramService::new(
         member_dao.clone(),
         tele_dao.clone(),
-        token_dao.clone(),
+        token_srv.clone(),
+        rule_srv.clone(),
+        tier_srv.clone(),
+        exemption_dao.clone(),
     ));
     telegram_srv.start().await;
 }
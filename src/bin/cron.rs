@@ -1,11 +1,20 @@
 use std::sync::Arc;
 
 use utxo_global_tgbot_api::{
-    repositories::{db::DB_POOL, member::MemberDao, telegram::TelegramDao, token::TokenDao},
-    services::telegram::TelegramService,
+    repositories::{
+        db::DB_POOL, exemption::ExemptionDao, member::MemberDao, rule::RuleDao,
+        telegram::TelegramDao, tier::TierDao, token::TokenDao,
+    },
+    services::{
+        member::MemberSrv, rule::RuleSrv, telegram::TelegramService, tier::TierSrv, token::TokenSrv,
+    },
 };
 
-async fn run_crons(telegram_svc: Arc<TelegramService>) {
+async fn run_crons(
+    telegram_svc: Arc<TelegramService>,
+    member_svc: Arc<MemberSrv>,
+    token_svc: Arc<TokenSrv>,
+) {
     /*
     let time_duration: u64 = 10;
     loop {
@@ -15,6 +24,10 @@ async fn run_crons(telegram_svc: Arc<TelegramService>) {
     */
 
     telegram_svc.cron_auto_kick_member().await;
+    telegram_svc.cron_reverify_members().await;
+    telegram_svc.cron_update_member_tiers().await;
+    member_svc.cron_retry_balance_checks().await;
+    token_svc.cron_refresh_tokens().await;
 }
 
 #[tokio::main]
@@ -22,15 +35,32 @@ async fn main() {
     let db = &DB_POOL.clone();
     let member_dao = Arc::new(MemberDao::new(db.clone()));
     let tele_dao = Arc::new(TelegramDao::new(db.clone()));
-    let token_dao = Arc::new(TokenDao::new(db.clone()));
+    let token_srv = Arc::new(TokenSrv::new(
+        TokenDao::new(db.clone()),
+        TelegramDao::new(db.clone()),
+    ));
+    let rule_srv = Arc::new(RuleSrv::new(RuleDao::new(db.clone())));
+    let tier_srv = Arc::new(TierSrv::new(TierDao::new(db.clone())));
+    let exemption_dao = Arc::new(ExemptionDao::new(db.clone()));
 
     // Initialize the bot
     let telegram_srv = Arc::new(TelegramService::new(
         member_dao.clone(),
         tele_dao.clone(),
-        token_dao.clone(),
+        token_srv.clone(),
+        rule_srv.clone(),
+        tier_srv.clone(),
+        exemption_dao.clone(),
+    ));
+
+    let member_srv = Arc::new(MemberSrv::new(
+        (*member_dao).clone(),
+        (*tele_dao).clone(),
+        (*rule_srv).clone(),
+        (*tier_srv).clone(),
+        (*exemption_dao).clone(),
     ));
 
     println!("Crons is running...");
-    run_crons(telegram_srv).await
+    run_crons(telegram_srv, member_srv, token_srv).await
 }
@@ -1,7 +1,9 @@
 use chrono::{Duration, TimeDelta};
+use ckb_jsonrpc_types::ScriptHashType;
 use config::Config;
 use dotenv::dotenv;
 use once_cell::sync::Lazy;
+use serde_derive::Deserialize;
 pub static CONFIG: Lazy<Config> = Lazy::new(|| {
     dotenv().ok();
     Config::builder()
@@ -15,5 +17,31 @@ pub fn get<'a, T: serde::Deserialize<'a>>(key: &str) -> T {
     CONFIG.get::<T>(key).unwrap()
 }
 
-pub const MEMBER_KYC_DURATION: TimeDelta = Duration::minutes(5);
-pub const MEMBER_BAN_DURATION: TimeDelta = Duration::minutes(15);
+/// Endpoints and deployed scripts of a chain, read from `[networks.<network>]`
+#[derive(Deserialize, Debug, Clone)]
+pub struct NetworkConfig {
+    pub explorer_api: String,
+    pub rpc: String,
+    pub indexer: String,
+    pub address_prefix: String,
+    pub xudt_code_hash: String,
+    pub xudt_hash_type: ScriptHashType,
+    pub spore_code_hash: String,
+    pub spore_hash_type: ScriptHashType,
+    pub cluster_code_hash: String,
+    pub unique_code_hash: String,
+    pub rgbpp_lock_code_hash: String,
+    pub btc_api: String,
+    pub btc_network: String,
+}
+
+pub static NETWORK_CONFIG: Lazy<NetworkConfig> = Lazy::new(|| {
+    let network: String = get("network");
+    get(&format!("networks.{}", network))
+});
+
+pub const DEFAULT_KYC_MINUTES: i32 = 5;
+pub const DEFAULT_BAN_MINUTES: i32 = 15;
+pub const MAX_BAN_DURATION: TimeDelta = Duration::days(7);
+pub const BALANCE_RETRY_BASE: TimeDelta = Duration::minutes(1);
+pub const BALANCE_RETRY_MAX: TimeDelta = Duration::hours(1);
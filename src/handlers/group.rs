@@ -0,0 +1,65 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{
+    config,
+    serialize::{
+        error::AppError,
+        group::{AddressListReq, AddressProofQuery, AddressProofResp},
+    },
+    services::rule::RuleSrv,
+};
+
+/// Group management endpoints are only available with the configured `admin_api_key`
+pub fn check_api_key(req: &HttpRequest) -> Result<(), AppError> {
+    let api_key = config::CONFIG
+        .get::<String>("admin_api_key")
+        .map_err(|_| AppError::new(403).message("Admin API is disabled"))?;
+
+    match req
+        .headers()
+        .get("x-api-key")
+        .and_then(|key| key.to_str().ok())
+    {
+        Some(key) if key == api_key => Ok(()),
+        _ => Err(AppError::new(401).message("Invalid API key")),
+    }
+}
+
+async fn set_address_list(
+    rule_srv: web::Data<RuleSrv>,
+    req: HttpRequest,
+    chat_id: web::Path<String>,
+    body: web::Json<AddressListReq>,
+) -> Result<HttpResponse, AppError> {
+    check_api_key(&req)?;
+    let body = body.into_inner();
+    let rule = rule_srv
+        .set_address_list(chat_id.into_inner(), body.addresses, body.root_only)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(rule))
+}
+
+/// Public, so members of groups with a root only list can fetch the proof of their address
+async fn get_address_proof(
+    rule_srv: web::Data<RuleSrv>,
+    chat_id: web::Path<String>,
+    query: web::Query<AddressProofQuery>,
+) -> Result<HttpResponse, AppError> {
+    let (merkle_root, proof) = rule_srv
+        .get_address_proof(chat_id.into_inner(), query.into_inner().address)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(AddressProofResp { merkle_root, proof }))
+}
+
+pub fn route(conf: &mut web::ServiceConfig) {
+    conf.service(
+        web::scope("/groups")
+            .route("/{chat_id}/address-list", web::post().to(set_address_list))
+            .route(
+                "/{chat_id}/address-list/proof",
+                web::get().to(get_address_proof),
+            ),
+    );
+}
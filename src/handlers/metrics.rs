@@ -0,0 +1,14 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{handlers::group::check_api_key, libs::http, serialize::error::AppError};
+
+/// Request, retry and failure counters per upstream host
+async fn upstream_metrics(req: HttpRequest) -> Result<HttpResponse, AppError> {
+    check_api_key(&req)?;
+
+    Ok(HttpResponse::Ok().json(http::metrics()))
+}
+
+pub fn route(conf: &mut web::ServiceConfig) {
+    conf.route("/metrics/upstreams", web::get().to(upstream_metrics));
+}
@@ -1,2 +1,5 @@
+pub mod group;
 pub mod member;
+pub mod metrics;
+pub mod token;
 pub mod welcome;
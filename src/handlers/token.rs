@@ -0,0 +1,106 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::{
+    handlers::group::check_api_key,
+    serialize::{
+        error::AppError,
+        token::{TokenDetailRes, TokenOverrideReq, TokenScriptReq, TokenSearchReq},
+    },
+    services::token::TokenSrv,
+};
+
+async fn list_tokens(
+    token_srv: web::Data<TokenSrv>,
+    req: HttpRequest,
+    query: web::Query<TokenSearchReq>,
+) -> Result<HttpResponse, AppError> {
+    check_api_key(&req)?;
+    let query = query.into_inner();
+    let tokens = token_srv
+        .search_tokens(query.q, query.limit, query.offset)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(tokens))
+}
+
+async fn register_token(
+    token_srv: web::Data<TokenSrv>,
+    req: HttpRequest,
+    body: web::Json<TokenScriptReq>,
+) -> Result<HttpResponse, AppError> {
+    check_api_key(&req)?;
+    let body = body.into_inner();
+    let token = token_srv
+        .register_token(body.code_hash, body.hash_type, body.args)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(token))
+}
+
+async fn get_token(
+    token_srv: web::Data<TokenSrv>,
+    req: HttpRequest,
+    type_hash: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    check_api_key(&req)?;
+    let type_hash = type_hash.into_inner();
+    let token = token_srv
+        .get_token(type_hash.clone())
+        .await
+        .ok_or_else(|| AppError::new(404).message("token not found"))?;
+    let groups = token_srv.get_groups_using(type_hash).await;
+
+    Ok(HttpResponse::Ok().json(TokenDetailRes { token, groups }))
+}
+
+async fn override_token(
+    token_srv: web::Data<TokenSrv>,
+    req: HttpRequest,
+    type_hash: web::Path<String>,
+    body: web::Json<TokenOverrideReq>,
+) -> Result<HttpResponse, AppError> {
+    check_api_key(&req)?;
+    let body = body.into_inner();
+    let token = token_srv
+        .override_token(type_hash.into_inner(), body.name, body.symbol, body.decimal)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(token))
+}
+
+async fn clear_overrides(
+    token_srv: web::Data<TokenSrv>,
+    req: HttpRequest,
+    type_hash: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    check_api_key(&req)?;
+    let token = token_srv.clear_overrides(type_hash.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(token))
+}
+
+async fn refresh_token(
+    token_srv: web::Data<TokenSrv>,
+    req: HttpRequest,
+    type_hash: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    check_api_key(&req)?;
+    let token = token_srv
+        .refresh_token(type_hash.into_inner())
+        .await
+        .ok_or_else(|| AppError::new(404).message("token metadata not found"))?;
+
+    Ok(HttpResponse::Ok().json(token))
+}
+
+pub fn route(conf: &mut web::ServiceConfig) {
+    conf.service(
+        web::scope("/tokens")
+            .route("", web::get().to(list_tokens))
+            .route("", web::post().to(register_token))
+            .route("/{type_hash}", web::get().to(get_token))
+            .route("/{type_hash}/override", web::put().to(override_token))
+            .route("/{type_hash}/override", web::delete().to(clear_overrides))
+            .route("/{type_hash}/refresh", web::post().to(refresh_token)),
+    );
+}
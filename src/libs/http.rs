@@ -0,0 +1,231 @@
+// Shared HTTP client for upstream APIs: timeouts, retries with jittered backoff on 429/5xx,
+// a token bucket per host and per-host failure counters. Responses can be recorded to and
+// replayed from fixtures, see `libs::replay`.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use once_cell::sync::Lazy;
+use reqwest::{header, Client, Request, Response, StatusCode};
+use serde_derive::Serialize;
+
+use crate::{
+    config,
+    libs::replay::{self, ReplayMode},
+    serialize::error::AppError,
+};
+
+pub const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+pub const DEFAULT_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_RATE_LIMIT_PER_SEC: f64 = 5.0;
+pub const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+pub const RETRY_MAX_DELAY: Duration = Duration::from_secs(10);
+
+pub static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(request_timeout())
+        .build()
+        .expect("Failed to build HTTP client")
+});
+
+static RATE_LIMITERS: Lazy<Mutex<HashMap<String, TokenBucket>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+static METRICS: Lazy<Mutex<HashMap<String, HostMetrics>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct HostMetrics {
+    pub requests: u64,
+    pub retries: u64,
+    pub failures: u64,
+    pub rate_limited: u64,
+    pub last_error: Option<String>,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+fn request_timeout() -> Duration {
+    let secs = config::CONFIG
+        .get::<u64>("http_timeout_secs")
+        .unwrap_or(DEFAULT_TIMEOUT_SECS);
+    Duration::from_secs(secs)
+}
+
+fn max_retries() -> u32 {
+    config::CONFIG
+        .get::<u32>("http_max_retries")
+        .unwrap_or(DEFAULT_MAX_RETRIES)
+}
+
+fn rate_limit() -> f64 {
+    config::CONFIG
+        .get::<f64>("http_rate_limit_per_sec")
+        .unwrap_or(DEFAULT_RATE_LIMIT_PER_SEC)
+}
+
+/// Send the request, or serve it from a recorded fixture in replay mode
+pub async fn execute(request: Request) -> Result<Response, AppError> {
+    let method = request.method().to_string();
+    let url = request.url().to_string();
+    let body = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map(<[u8]>::to_vec);
+
+    match replay::mode() {
+        ReplayMode::Off => send(request).await,
+        ReplayMode::Record => {
+            let response = send(request).await?;
+            let status = response.status().as_u16();
+            let text = response
+                .text()
+                .await
+                .map_err(|error| AppError::new(502).cause(error))?;
+            replay::save(&method, &url, body.as_deref(), status, &text);
+            fixture_response(status, text)
+        }
+        ReplayMode::Replay => {
+            let fixture = replay::load(&method, &url, body.as_deref()).ok_or_else(|| {
+                AppError::new(502).message(&format!("No fixture recorded for {} {}", method, url))
+            })?;
+            fixture_response(fixture.status, fixture.body)
+        }
+    }
+}
+
+fn fixture_response(status: u16, body: String) -> Result<Response, AppError> {
+    ::http::Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .map(Response::from)
+        .map_err(|error| AppError::new(500).message(&error.to_string()))
+}
+
+/// Send the request, waiting for the host's rate limit and retrying transient failures
+async fn send(request: Request) -> Result<Response, AppError> {
+    let host = request.url().host_str().unwrap_or_default().to_owned();
+    let max_retries = max_retries();
+    let mut attempt = 0;
+
+    loop {
+        acquire(&host).await;
+        let Some(current) = request.try_clone() else {
+            return Err(AppError::new(500).message("request body cannot be retried"));
+        };
+        record(&host, |metrics| metrics.requests += 1);
+
+        let retry_after = match HTTP_CLIENT.execute(current).await {
+            Ok(response) if is_retryable(response.status()) => {
+                let status = response.status();
+                if status == StatusCode::TOO_MANY_REQUESTS {
+                    record(&host, |metrics| metrics.rate_limited += 1);
+                }
+                if attempt >= max_retries {
+                    record_failure(&host, &format!("HTTP {}", status));
+                    return Ok(response);
+                }
+                retry_after(&response)
+            }
+            Ok(response) => return Ok(response),
+            Err(error) => {
+                if attempt >= max_retries || !(error.is_timeout() || error.is_connect()) {
+                    record_failure(&host, &error.to_string());
+                    return Err(AppError::new(502).cause(error));
+                }
+                None
+            }
+        };
+
+        record(&host, |metrics| metrics.retries += 1);
+        tokio::time::sleep(retry_after.unwrap_or_else(|| backoff(attempt))).await;
+        attempt += 1;
+    }
+}
+
+/// Counters of every upstream host contacted so far
+pub fn metrics() -> HashMap<String, HostMetrics> {
+    METRICS.lock().unwrap().clone()
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    let secs = response
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse::<u64>()
+        .ok()?;
+    Some(Duration::from_secs(secs).min(RETRY_MAX_DELAY))
+}
+
+/// Exponential backoff with up to 50% random jitter
+fn backoff(attempt: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(RETRY_MAX_DELAY);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos())
+        .unwrap_or(0);
+    let jitter = delay.mul_f64((nanos % 1000) as f64 / 2000.0);
+
+    delay + jitter
+}
+
+/// Take a token from the host's bucket, sleeping until one is available
+async fn acquire(host: &str) {
+    let rate = rate_limit();
+    if rate <= 0.0 {
+        return;
+    }
+
+    loop {
+        let wait = {
+            let mut buckets = RATE_LIMITERS.lock().unwrap();
+            let bucket = buckets.entry(host.to_owned()).or_insert(TokenBucket {
+                tokens: rate,
+                updated_at: Instant::now(),
+            });
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+            bucket.updated_at = now;
+
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return;
+            }
+            Duration::from_secs_f64((1.0 - bucket.tokens) / rate)
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+fn record(host: &str, update: impl FnOnce(&mut HostMetrics)) {
+    let mut metrics = METRICS.lock().unwrap();
+    update(metrics.entry(host.to_owned()).or_default());
+}
+
+fn record_failure(host: &str, error: &str) {
+    log::error!("Request to {} failed: {}", host, error);
+    record(host, |metrics| {
+        metrics.failures += 1;
+        metrics.last_error = Some(error.to_owned());
+    });
+}
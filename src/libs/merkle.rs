@@ -0,0 +1,193 @@
+// Merkle tree over CKB addresses, used for snapshot allowlists.
+//
+// Leaves are the blake2b hashes of the addresses and every parent hashes its two children in
+// sorted order, so a proof is just the list of sibling hashes from the leaf up to the root.
+// An odd node out is carried up to the next level as is and adds nothing to the proof.
+
+use ckb_hash::blake2b_256;
+
+fn hash_leaf(address: &str) -> [u8; 32] {
+    blake2b_256(address.trim().as_bytes())
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let (first, second) = if left <= right {
+        (left, right)
+    } else {
+        (right, left)
+    };
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(first);
+    data[32..].copy_from_slice(second);
+    blake2b_256(data)
+}
+
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_pair(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn decode_hash(hash: &str) -> Option<[u8; 32]> {
+    let bytes = hex::decode(hash.trim_start_matches("0x")).ok()?;
+    <[u8; 32]>::try_from(bytes.as_slice()).ok()
+}
+
+fn encode_hash(hash: &[u8; 32]) -> String {
+    format!("0x{}", hex::encode(hash))
+}
+
+fn leaves(addresses: &[String]) -> Vec<[u8; 32]> {
+    let mut leaves = addresses
+        .iter()
+        .map(|address| hash_leaf(address))
+        .collect::<Vec<[u8; 32]>>();
+    leaves.sort();
+    leaves.dedup();
+    leaves
+}
+
+/// Hex encoded leaves of the tree, which can be stored to prove addresses without keeping them
+pub fn leaf_hashes(addresses: &[String]) -> Vec<String> {
+    leaves(addresses).iter().map(encode_hash).collect()
+}
+
+/// Hex encoded leaf of the address
+pub fn leaf_hash(address: &str) -> String {
+    encode_hash(&hash_leaf(address))
+}
+
+/// Hex encoded root of the tree, `None` for an empty list
+pub fn merkle_root(addresses: &[String]) -> Option<String> {
+    let mut level = leaves(addresses);
+    if level.is_empty() {
+        return None;
+    }
+
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+
+    Some(encode_hash(&level[0]))
+}
+
+/// Proof of the address in the tree over the hex encoded leaves, `None` if it is not a leaf
+pub fn merkle_proof(leaf_hashes: &[String], address: &str) -> Option<Vec<String>> {
+    let mut level = leaf_hashes
+        .iter()
+        .filter_map(|leaf| decode_hash(leaf))
+        .collect::<Vec<[u8; 32]>>();
+    level.sort();
+    level.dedup();
+
+    let mut index = level.binary_search(&hash_leaf(address)).ok()?;
+    let mut proof = vec![];
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(encode_hash(sibling));
+        }
+        index /= 2;
+        level = next_level(&level);
+    }
+
+    Some(proof)
+}
+
+pub fn verify_proof(root: &str, address: &str, proof: &[String]) -> bool {
+    let mut hash = hash_leaf(address);
+    for sibling in proof {
+        let Some(sibling) = decode_hash(sibling) else {
+            return false;
+        };
+        hash = hash_pair(&hash, &sibling);
+    }
+
+    encode_hash(&hash) == root.to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("ckt1address{}", i)).collect()
+    }
+
+    #[test]
+    fn root_of_empty_list_is_none() {
+        assert_eq!(merkle_root(&[]), None);
+        assert_eq!(merkle_proof(&[], "ckt1address0"), None);
+    }
+
+    #[test]
+    fn root_of_single_address_is_its_leaf() {
+        let list = addresses(1);
+        assert_eq!(merkle_root(&list), Some(leaf_hash(&list[0])));
+        assert_eq!(merkle_proof(&leaf_hashes(&list), &list[0]), Some(vec![]));
+    }
+
+    #[test]
+    fn root_ignores_order_duplicates_and_whitespace() {
+        let list = addresses(5);
+        let mut shuffled = list.iter().rev().cloned().collect::<Vec<String>>();
+        shuffled.push(format!(" {} ", list[2]));
+        assert_eq!(merkle_root(&list), merkle_root(&shuffled));
+    }
+
+    #[test]
+    fn every_listed_address_has_a_valid_proof() {
+        for count in 1..=9 {
+            let list = addresses(count);
+            let root = merkle_root(&list).unwrap();
+            let leaves = leaf_hashes(&list);
+            for address in &list {
+                let proof = merkle_proof(&leaves, address).unwrap();
+                assert!(
+                    verify_proof(&root, address, &proof),
+                    "{} of {}",
+                    address,
+                    count
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unlisted_address_has_no_proof_and_does_not_verify() {
+        let list = addresses(4);
+        let root = merkle_root(&list).unwrap();
+        let leaves = leaf_hashes(&list);
+        let proof = merkle_proof(&leaves, &list[0]).unwrap();
+
+        assert_eq!(merkle_proof(&leaves, "ckt1stranger"), None);
+        assert!(!verify_proof(&root, "ckt1stranger", &proof));
+    }
+
+    #[test]
+    fn tampered_or_malformed_proof_does_not_verify() {
+        let list = addresses(4);
+        let root = merkle_root(&list).unwrap();
+        let mut proof = merkle_proof(&leaf_hashes(&list), &list[1]).unwrap();
+
+        assert!(!verify_proof(&root, &list[1], &proof[1..]));
+        assert!(!verify_proof(&root, &list[1], &["0x1234".to_owned()]));
+        proof[0] = leaf_hash("ckt1stranger");
+        assert!(!verify_proof(&root, &list[1], &proof));
+    }
+
+    #[test]
+    fn root_comparison_is_case_insensitive() {
+        let list = addresses(3);
+        let root = merkle_root(&list)
+            .unwrap()
+            .to_uppercase()
+            .replacen("0X", "0x", 1);
+        let proof = merkle_proof(&leaf_hashes(&list), &list[2]).unwrap();
+        assert!(verify_proof(&root, &list[2], &proof));
+    }
+}
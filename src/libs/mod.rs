@@ -1 +1,5 @@
+pub mod http;
+pub mod merkle;
+pub mod molecule;
+pub mod replay;
 pub mod signer;
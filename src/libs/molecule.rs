@@ -0,0 +1,147 @@
+// Minimal readers for molecule encoded cell data: tables, `Bytes` and `BytesOpt`.
+//
+// Cell data is untrusted, so every size and offset is checked against the data before use.
+
+fn read_u32(data: &[u8], offset: usize) -> Option<usize> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?) as usize)
+}
+
+/// Raw bytes of the table's `index`-th field, `None` if the table has fewer fields or is malformed
+pub fn table_field(data: &[u8], index: usize) -> Option<&[u8]> {
+    let total_size = read_u32(data, 0)?;
+    if total_size > data.len() {
+        return None;
+    }
+    // A table without fields is just its size
+    if total_size == 4 {
+        return None;
+    }
+
+    let header_size = read_u32(data, 4)?;
+    if header_size % 4 != 0 || header_size > total_size {
+        return None;
+    }
+    let field_count = (header_size / 4).checked_sub(1)?;
+    if index >= field_count {
+        return None;
+    }
+
+    let start = read_u32(data, 4 + index * 4)?;
+    let end = if index + 1 < field_count {
+        read_u32(data, 8 + index * 4)?
+    } else {
+        total_size
+    };
+    if start < header_size || start > end || end > total_size {
+        return None;
+    }
+    data.get(start..end)
+}
+
+/// Content of a `Bytes` vector: a u32 length followed by the bytes
+pub fn bytes(data: &[u8]) -> Option<&[u8]> {
+    let length = read_u32(data, 0)?;
+    data.get(4..length.checked_add(4)?)
+}
+
+/// Content of a `BytesOpt`, which is empty when unset
+pub fn bytes_opt(data: &[u8]) -> Option<&[u8]> {
+    if data.is_empty() {
+        return None;
+    }
+    bytes(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_bytes(content: &[u8]) -> Vec<u8> {
+        let mut data = (content.len() as u32).to_le_bytes().to_vec();
+        data.extend_from_slice(content);
+        data
+    }
+
+    fn encode_table(fields: &[Vec<u8>]) -> Vec<u8> {
+        let header_size = 4 * (1 + fields.len());
+        let total_size = header_size + fields.iter().map(Vec::len).sum::<usize>();
+        let mut data = (total_size as u32).to_le_bytes().to_vec();
+        let mut offset = header_size;
+        for field in fields {
+            data.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += field.len();
+        }
+        for field in fields {
+            data.extend_from_slice(field);
+        }
+        data
+    }
+
+    #[test]
+    fn table_without_fields() {
+        let data = encode_table(&[]);
+        assert_eq!(data, 4u32.to_le_bytes());
+        assert_eq!(table_field(&data, 0), None);
+    }
+
+    #[test]
+    fn table_with_one_field() {
+        let data = encode_table(&[encode_bytes(b"spore")]);
+        assert_eq!(table_field(&data, 0).and_then(bytes), Some(&b"spore"[..]));
+        assert_eq!(table_field(&data, 1), None);
+    }
+
+    #[test]
+    fn table_with_three_fields() {
+        let data = encode_table(&[encode_bytes(b"image/png"), encode_bytes(b"content"), vec![]]);
+        assert_eq!(
+            table_field(&data, 0).and_then(bytes),
+            Some(&b"image/png"[..])
+        );
+        assert_eq!(table_field(&data, 1).and_then(bytes), Some(&b"content"[..]));
+        assert_eq!(table_field(&data, 2).and_then(bytes_opt), None);
+        assert_eq!(table_field(&data, 3), None);
+    }
+
+    #[test]
+    fn set_bytes_opt() {
+        let cluster_id = [7u8; 32];
+        let data = encode_table(&[vec![], vec![], encode_bytes(&cluster_id)]);
+        assert_eq!(
+            table_field(&data, 2).and_then(bytes_opt),
+            Some(&cluster_id[..])
+        );
+    }
+
+    #[test]
+    fn truncated_input() {
+        let data = encode_table(&[encode_bytes(b"name"), encode_bytes(b"description")]);
+        for len in 0..data.len() {
+            assert_eq!(table_field(&data[..len], 1), None, "length {}", len);
+        }
+        assert_eq!(bytes(&encode_bytes(b"name")[..6]), None);
+        assert_eq!(bytes(&[1, 0]), None);
+    }
+
+    #[test]
+    fn malformed_header() {
+        // Offsets before the header, past the end, out of order, or a header of zero fields
+        let mut data = encode_table(&[encode_bytes(b"a"), encode_bytes(b"b")]);
+        data[4..8].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(table_field(&data, 0), None);
+
+        let mut data = encode_table(&[encode_bytes(b"a"), encode_bytes(b"b")]);
+        data[8..12].copy_from_slice(&100u32.to_le_bytes());
+        assert_eq!(table_field(&data, 0), None);
+        assert_eq!(table_field(&data, 1), None);
+
+        let mut data = encode_table(&[encode_bytes(b"a"), encode_bytes(b"b")]);
+        data[8..12].copy_from_slice(&12u32.to_le_bytes());
+        data[4..8].copy_from_slice(&17u32.to_le_bytes());
+        assert_eq!(table_field(&data, 0), None);
+
+        let data = [8u8, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(table_field(&data, 0), None);
+    }
+}
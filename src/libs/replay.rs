@@ -0,0 +1,107 @@
+// Record-and-replay of upstream HTTP calls. In `record` mode every response is saved as a
+// fixture file, in `replay` mode responses are served from those files and the network is
+// never touched.
+//
+// Only requests sent through `libs::http` are covered: the explorer, BTC and DID APIs and the
+// chatbot. The CKB node (`CkbRpcClient` of the rpc balance provider and the token metadata
+// lookups) and the EVM RPC of the ERC-20/ERC-721 rules use their own transports and still go
+// to the network in replay mode. Pair replay with `balance_provider = 'explorer'` or
+// `'fixture'` for fully offline runs.
+
+use std::{fs, path::PathBuf};
+
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config;
+
+pub const REPLAY_MODE_OFF: &str = "off";
+pub const REPLAY_MODE_RECORD: &str = "record";
+pub const REPLAY_MODE_REPLAY: &str = "replay";
+pub const DEFAULT_FIXTURES_DIR: &str = "fixtures/http";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    Off,
+    Record,
+    Replay,
+}
+
+/// A recorded upstream exchange
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Fixture {
+    pub method: String,
+    pub url: String,
+    pub request_body: Option<String>,
+    pub status: u16,
+    pub body: String,
+}
+
+/// Mode selected by `http_replay_mode` in config, `off` by default
+pub fn mode() -> ReplayMode {
+    let mode = config::CONFIG
+        .get::<String>("http_replay_mode")
+        .unwrap_or(REPLAY_MODE_OFF.to_owned());
+    match mode.as_str() {
+        REPLAY_MODE_RECORD => ReplayMode::Record,
+        REPLAY_MODE_REPLAY => ReplayMode::Replay,
+        _ => ReplayMode::Off,
+    }
+}
+
+fn fixtures_dir() -> PathBuf {
+    let dir = config::CONFIG
+        .get::<String>("http_fixtures_dir")
+        .unwrap_or(DEFAULT_FIXTURES_DIR.to_owned());
+    PathBuf::from(dir)
+}
+
+/// Fixtures are grouped by host and named after a hash of the method, URL and body, so the
+/// same request always maps to the same file
+fn fixture_path(method: &str, url: &str, request_body: Option<&[u8]>) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update(b" ");
+    hasher.update(url.as_bytes());
+    if let Some(body) = request_body {
+        hasher.update(b"\n");
+        hasher.update(body);
+    }
+    let host = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or("unknown".to_owned());
+
+    fixtures_dir()
+        .join(host)
+        .join(format!("{}.json", hex::encode(hasher.finalize())))
+}
+
+pub fn load(method: &str, url: &str, request_body: Option<&[u8]>) -> Option<Fixture> {
+    let path = fixture_path(method, url, request_body);
+    let data = fs::read_to_string(&path).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+pub fn save(method: &str, url: &str, request_body: Option<&[u8]>, status: u16, body: &str) {
+    let path = fixture_path(method, url, request_body);
+    let fixture = Fixture {
+        method: method.to_owned(),
+        url: url.to_owned(),
+        request_body: request_body.map(|body| String::from_utf8_lossy(body).into_owned()),
+        status,
+        body: body.to_owned(),
+    };
+
+    let saved = path
+        .parent()
+        .map(fs::create_dir_all)
+        .unwrap_or(Ok(()))
+        .and_then(|_| {
+            let data = serde_json::to_string_pretty(&fixture).unwrap_or_default();
+            fs::write(&path, data)
+        });
+    if let Err(err) = saved {
+        log::error!("Recording fixture {} failed: {}", path.display(), err);
+    }
+}
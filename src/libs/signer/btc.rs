@@ -4,7 +4,7 @@ use super::types;
 use bitcoin::{
     secp256k1,
     sign_message::{signed_msg_hash, MessageSignature},
-    PublicKey,
+    Address, CompressedPublicKey, Network, PublicKey,
 };
 
 pub fn verify_message(challenge: &str, data: types::SignData) -> bool {
@@ -21,3 +21,26 @@ pub fn verify_message(challenge: &str, data: types::SignData) -> bool {
 
     recover_public_key.inner.to_string() == public_key.inner.to_string()
 }
+
+/// Whether `address` is one of the P2PKH, P2SH-P2WPKH, P2WPKH or P2TR addresses of the
+/// signer's public key
+pub fn is_address_of(identity: &str, address: &str, network: Network) -> bool {
+    let Ok(public_key_bytes) = hex::decode(identity.replace("0x", "")) else {
+        return false;
+    };
+    let Ok(public_key) = PublicKey::from_slice(&public_key_bytes) else {
+        return false;
+    };
+
+    let mut addresses = vec![Address::p2pkh(public_key, network)];
+    if let Ok(compressed) = CompressedPublicKey::try_from(public_key) {
+        addresses.push(Address::p2shwpkh(&compressed, network));
+        addresses.push(Address::p2wpkh(&compressed, network));
+    }
+    let secp = secp256k1::Secp256k1::verification_only();
+    addresses.push(Address::p2tr(&secp, public_key.into(), None, network));
+
+    addresses
+        .iter()
+        .any(|candidate| candidate.to_string() == address)
+}
@@ -0,0 +1,12 @@
+use chrono::NaiveDateTime;
+use serde_derive::{Deserialize, Serialize};
+use tokio_pg_mapper_derive::PostgresMapper;
+
+/// Balances of a lock script as last fetched from the balance provider
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PostgresMapper)]
+#[pg_mapper(table = "balance_cache")]
+pub struct CachedBalance {
+    pub lock_hash: String,
+    pub balances: String,
+    pub fetched_at: NaiveDateTime,
+}
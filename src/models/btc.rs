@@ -0,0 +1,8 @@
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BtcUtxo {
+    pub txid: String,
+    pub vout: u32,
+    pub value: u64,
+}
@@ -61,6 +61,21 @@ pub struct TokenInfo {
     pub full_name: Option<String>,
     pub udt_type: Option<String>,
     pub type_script: Option<UdtTypeScript>,
+    #[serde(default)]
+    pub type_hash: Option<String>,
+    #[serde(default)]
+    pub holders_count: Option<serde_json::Value>,
+}
+
+impl TokenInfo {
+    /// Holder count, which the explorer reports either as a number or a string
+    pub fn holders(&self) -> Option<u64> {
+        match self.holders_count.as_ref()? {
+            serde_json::Value::Number(count) => count.as_u64(),
+            serde_json::Value::String(count) => count.parse().ok(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -76,6 +91,11 @@ pub struct TokenResponse {
     pub data: TokenData,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct TokenListResponse {
+    pub data: Vec<TokenData>,
+}
+
 #[derive(Deserialize, Debug)]
 pub struct NFTTypeScript {
     pub args: String,
@@ -90,3 +110,60 @@ pub struct NFTInfo {
     pub standard: String,
     pub type_script: NFTTypeScript,
 }
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct XudtInfo {
+    pub symbol: Option<String>,
+    pub amount: Option<String>,
+    pub decimal: Option<String>,
+    pub type_hash: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct DisplayCell {
+    pub address_hash: Option<String>,
+    pub capacity: Option<String>,
+    pub cell_type: Option<String>,
+    pub xudt_info: Option<XudtInfo>,
+    pub udt_info: Option<XudtInfo>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TransactionAttributes {
+    pub transaction_hash: Option<String>,
+    pub block_number: Option<String>,
+    pub block_timestamp: Option<String>,
+    pub income: Option<String>,
+    #[serde(default)]
+    pub display_inputs: Vec<DisplayCell>,
+    #[serde(default)]
+    pub display_outputs: Vec<DisplayCell>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TransactionData {
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub data_type: Option<String>,
+    pub attributes: TransactionAttributes,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TransactionsResponse {
+    pub data: Vec<TransactionData>,
+}
+
+/// Token info of an xUDT, as encoded in the unique cell created with its first issuance
+#[derive(Deserialize, Debug, Clone)]
+pub struct XudtMetadata {
+    pub decimal: u8,
+    pub name: String,
+    pub symbol: String,
+}
+
+/// Name and description stored in a spore cluster cell
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClusterMetadata {
+    pub name: String,
+    pub description: String,
+}
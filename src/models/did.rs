@@ -0,0 +1,19 @@
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DidAccount {
+    pub account: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DidAccountList {
+    #[serde(default)]
+    pub account_list: Vec<DidAccount>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DidAccountListResponse {
+    pub errno: i64,
+    pub errmsg: Option<String>,
+    pub data: Option<DidAccountList>,
+}
@@ -0,0 +1,60 @@
+use chrono::NaiveDateTime;
+use serde_derive::{Deserialize, Serialize};
+use tokio_pg_mapper_derive::PostgresMapper;
+
+pub enum ExemptionKind {
+    TelegramId,
+    Username,
+    CkbAddress,
+}
+
+pub const EXEMPTION_KIND_TELEGRAM_ID: i16 = ExemptionKind::TelegramId as i16;
+pub const EXEMPTION_KIND_USERNAME: i16 = ExemptionKind::Username as i16;
+pub const EXEMPTION_KIND_CKB_ADDRESS: i16 = ExemptionKind::CkbAddress as i16;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PostgresMapper)]
+#[pg_mapper(table = "tg_group_exemptions")]
+pub struct GroupExemption {
+    pub chat_id: String,
+    pub kind: i16,
+    pub value: String,
+
+    #[serde(skip_serializing)]
+    pub created_at: NaiveDateTime,
+
+    #[serde(skip_serializing)]
+    pub updated_at: NaiveDateTime,
+}
+
+impl GroupExemption {
+    /// Build an exemption from admin input: a numeric Telegram id, a CKB address or a username
+    pub fn parse(chat_id: String, value: &str, now: NaiveDateTime) -> Self {
+        let value = value.trim();
+        let (kind, value) = if value.parse::<i64>().is_ok() {
+            (EXEMPTION_KIND_TELEGRAM_ID, value.to_owned())
+        } else if value.starts_with("ckb1") || value.starts_with("ckt1") {
+            (EXEMPTION_KIND_CKB_ADDRESS, value.to_owned())
+        } else {
+            (
+                EXEMPTION_KIND_USERNAME,
+                value.trim_start_matches('@').to_lowercase(),
+            )
+        };
+
+        GroupExemption {
+            chat_id,
+            kind,
+            value,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self.kind {
+            EXEMPTION_KIND_TELEGRAM_ID => format!("id {}", self.value),
+            EXEMPTION_KIND_USERNAME => format!("@{}", self.value),
+            _ => self.value.clone(),
+        }
+    }
+}
@@ -1,4 +1,10 @@
+pub mod balance;
+pub mod btc;
 pub mod ckb;
+pub mod did;
+pub mod exemption;
 pub mod member;
+pub mod rule;
 pub mod telegram;
+pub mod tier;
 pub mod token;
@@ -0,0 +1,58 @@
+use chrono::{NaiveDateTime, Utc};
+use serde_derive::{Deserialize, Serialize};
+use tokio_pg_mapper_derive::PostgresMapper;
+
+pub enum RuleType {
+    HoldingPeriod,
+    AddressList,
+    MinTransactions,
+    MinAddressAge,
+    Erc20Balance,
+    Erc721Ownership,
+    DidAccount,
+}
+
+pub const RULE_TYPE_HOLDING_PERIOD: i16 = RuleType::HoldingPeriod as i16;
+pub const RULE_TYPE_ADDRESS_LIST: i16 = RuleType::AddressList as i16;
+pub const RULE_TYPE_MIN_TRANSACTIONS: i16 = RuleType::MinTransactions as i16;
+pub const RULE_TYPE_MIN_ADDRESS_AGE: i16 = RuleType::MinAddressAge as i16;
+pub const RULE_TYPE_ERC20_BALANCE: i16 = RuleType::Erc20Balance as i16;
+pub const RULE_TYPE_ERC721_OWNERSHIP: i16 = RuleType::Erc721Ownership as i16;
+pub const RULE_TYPE_DID_ACCOUNT: i16 = RuleType::DidAccount as i16;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PostgresMapper)]
+#[pg_mapper(table = "tg_group_rules")]
+pub struct GroupRule {
+    pub id: i32,
+    pub chat_id: String,
+    pub rule_type: i16,
+    pub token_address: Option<String>,
+    pub min_amount: Option<i64>,
+    pub min_days: Option<i32>,
+    pub merkle_root: Option<String>,
+    pub chain_id: Option<i64>,
+
+    #[serde(skip_serializing)]
+    pub created_at: NaiveDateTime,
+
+    #[serde(skip_serializing)]
+    pub updated_at: NaiveDateTime,
+}
+
+impl GroupRule {
+    pub fn new(chat_id: String, rule_type: i16) -> Self {
+        let now = Utc::now().naive_utc();
+        GroupRule {
+            id: 0,
+            chat_id,
+            rule_type,
+            token_address: None,
+            min_amount: None,
+            min_days: None,
+            merkle_root: None,
+            chain_id: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
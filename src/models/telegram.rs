@@ -1,16 +1,61 @@
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{Duration, NaiveDate, NaiveDateTime, TimeDelta};
 use serde_derive::{Deserialize, Serialize};
 use tokio_pg_mapper_derive::PostgresMapper;
 
+use crate::config::{DEFAULT_BAN_MINUTES, DEFAULT_KYC_MINUTES, MAX_BAN_DURATION};
+
+pub enum GroupStatus {
+    Inactive,
+    Active,
+}
+
+/// Groups turn inactive once the bot is removed from them
+pub const GROUP_STATUS_INACTIVE: i16 = GroupStatus::Inactive as i16;
+pub const GROUP_STATUS_ACTIVE: i16 = GroupStatus::Active as i16;
+
 pub enum GroupMemberStatus {
     Pending,
     Accepted,
     Rejected,
+    Lapsed,
+    Left,
+    Kicked,
+    Banned,
 }
 
 pub const MEMBER_STATUS_PENDING: i16 = GroupMemberStatus::Pending as i16;
 pub const MEMBER_STATUS_ACCEPTED: i16 = GroupMemberStatus::Accepted as i16;
 pub const MEMBER_STATUS_REJECT: i16 = GroupMemberStatus::Rejected as i16;
+pub const MEMBER_STATUS_LAPSED: i16 = GroupMemberStatus::Lapsed as i16;
+pub const MEMBER_STATUS_LEFT: i16 = GroupMemberStatus::Left as i16;
+pub const MEMBER_STATUS_KICKED: i16 = GroupMemberStatus::Kicked as i16;
+pub const MEMBER_STATUS_BANNED: i16 = GroupMemberStatus::Banned as i16;
+
+/// Whether a member with this status is still in the group
+pub fn is_member_present(status: i16) -> bool {
+    !matches!(
+        status,
+        MEMBER_STATUS_LEFT | MEMBER_STATUS_KICKED | MEMBER_STATUS_BANNED
+    )
+}
+
+pub enum LapseAction {
+    Restrict,
+    Remove,
+}
+
+pub const LAPSE_ACTION_RESTRICT: i16 = LapseAction::Restrict as i16;
+pub const LAPSE_ACTION_REMOVE: i16 = LapseAction::Remove as i16;
+
+/// How new members are gated: restricted after they join, or kept out through a chat join
+/// request until they pass verification
+pub enum JoinMode {
+    Restrict,
+    Request,
+}
+
+pub const JOIN_MODE_RESTRICT: i16 = JoinMode::Restrict as i16;
+pub const JOIN_MODE_REQUEST: i16 = JoinMode::Request as i16;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PostgresMapper)]
 #[pg_mapper(table = "tg_groups")]
@@ -21,6 +66,11 @@ pub struct TelegramGroup {
     pub token_address: Option<String>,
     pub min_approve_balance: Option<i64>,
     pub min_approve_age: Option<i32>,
+    pub grace_hours: Option<i32>,
+    pub lapse_action: i16,
+    pub kyc_minutes: Option<i32>,
+    pub ban_minutes: Option<i32>,
+    pub join_mode: i16,
 
     #[serde(skip_serializing)]
     pub created_at: NaiveDateTime,
@@ -29,11 +79,46 @@ pub struct TelegramGroup {
     pub updated_at: NaiveDateTime,
 }
 
+impl TelegramGroup {
+    /// Time a new member has to complete verification
+    pub fn kyc_duration(&self) -> TimeDelta {
+        Duration::minutes(self.kyc_minutes.unwrap_or(DEFAULT_KYC_MINUTES).max(1) as i64)
+    }
+
+    /// Ban cooldown for a member's `fail_count`-th failure; it doubles with every repeated
+    /// failure, up to `MAX_BAN_DURATION`
+    pub fn ban_duration(&self, fail_count: i32) -> TimeDelta {
+        let base = Duration::minutes(self.ban_minutes.unwrap_or(DEFAULT_BAN_MINUTES).max(1) as i64);
+        let factor = 1 << (fail_count - 1).clamp(0, 10);
+        (base * factor).min(MAX_BAN_DURATION)
+    }
+}
+
+/// Human readable duration such as `15 minutes` or `2 hours`
+pub fn format_duration(duration: TimeDelta) -> String {
+    let minutes = duration.num_minutes();
+    let (value, unit) = if minutes >= 1440 && minutes % 1440 == 0 {
+        (minutes / 1440, "day")
+    } else if minutes >= 60 && minutes % 60 == 0 {
+        (minutes / 60, "hour")
+    } else {
+        (minutes, "minute")
+    };
+
+    if value == 1 {
+        format!("{} {}", value, unit)
+    } else {
+        format!("{} {}s", value, unit)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PostgresMapper)]
 #[pg_mapper(table = "tg_group_admins")]
 pub struct TelegramGroupAdmin {
     pub chat_id: String,
     pub user_id: i64,
+    /// When the admin last picked the group to manage from private chat
+    pub selected_at: Option<NaiveDateTime>,
 
     #[serde(skip_serializing)]
     pub created_at: NaiveDateTime,
@@ -53,6 +138,15 @@ pub struct TelegramGroupJoined {
     pub status: i16,
     pub balances: Option<String>,
     pub expired: NaiveDateTime,
+    pub tier_id: Option<i32>,
+    pub checked_at: Option<NaiveDateTime>,
+    pub warned_at: Option<NaiveDateTime>,
+    pub fail_count: i32,
+    pub evm_address: Option<String>,
+    pub btc_address: Option<String>,
+    pub balance_retries: i32,
+    pub retry_at: Option<NaiveDateTime>,
+    pub via_request: bool,
 
     #[serde(skip_serializing)]
     pub created_at: NaiveDateTime,
@@ -0,0 +1,53 @@
+use chrono::NaiveDateTime;
+use serde_derive::{Deserialize, Serialize};
+use tokio_pg_mapper_derive::PostgresMapper;
+
+pub const TIER_PERMISSION_TEXT: i16 = 1;
+pub const TIER_PERMISSION_MEDIA: i16 = 1 << 1;
+/// Telegram has no permission to send links, only to show their previews
+pub const TIER_PERMISSION_PREVIEWS: i16 = 1 << 2;
+pub const TIER_PERMISSION_POLLS: i16 = 1 << 3;
+
+pub const TIER_PERMISSIONS: [(&str, i16); 4] = [
+    ("text", TIER_PERMISSION_TEXT),
+    ("media", TIER_PERMISSION_MEDIA),
+    ("previews", TIER_PERMISSION_PREVIEWS),
+    ("polls", TIER_PERMISSION_POLLS),
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PostgresMapper)]
+#[pg_mapper(table = "tg_group_tiers")]
+pub struct GroupTier {
+    pub id: i32,
+    pub chat_id: String,
+    pub name: String,
+    pub token_address: Option<String>,
+    pub min_balance: Option<i64>,
+    pub permissions: i16,
+
+    #[serde(skip_serializing)]
+    pub created_at: NaiveDateTime,
+
+    #[serde(skip_serializing)]
+    pub updated_at: NaiveDateTime,
+}
+
+impl GroupTier {
+    /// Parse a comma separated permission list such as `text,media,previews`
+    pub fn parse_permissions(value: &str) -> Option<i16> {
+        let mut permissions = TIER_PERMISSION_TEXT;
+        for name in value.split(',').map(|name| name.trim().to_lowercase()) {
+            let (_, flag) = TIER_PERMISSIONS.iter().find(|(key, _)| *key == name)?;
+            permissions |= flag;
+        }
+        Some(permissions)
+    }
+
+    pub fn permission_names(&self) -> Vec<&'static str> {
+        TIER_PERMISSIONS
+            .iter()
+            .filter(|(_, flag)| self.permissions & flag != 0)
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
@@ -11,6 +11,11 @@ pub enum TokenType {
 pub const TOKEN_TYPE_XUDT: i16 = TokenType::Xudt as i16;
 pub const TOKEN_TYPE_SPORE: i16 = TokenType::Spore as i16;
 
+/// Metadata fields an admin can override; refreshes leave overridden fields untouched
+pub const TOKEN_FIELD_NAME: &str = "name";
+pub const TOKEN_FIELD_SYMBOL: &str = "symbol";
+pub const TOKEN_FIELD_DECIMAL: &str = "decimal";
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, PostgresMapper)]
 #[pg_mapper(table = "tokens")]
 pub struct Token {
@@ -23,6 +28,8 @@ pub struct Token {
     pub args: String,
     pub code_hash: String,
     pub hash_type: String,
+    pub overridden: Vec<String>,
+    pub refreshed_at: Option<NaiveDateTime>,
 
     #[serde(skip_serializing)]
     pub created_at: NaiveDateTime,
@@ -44,8 +51,59 @@ impl Token {
             args: String::new(),
             code_hash: String::new(),
             hash_type: String::new(),
+            overridden: vec![],
+            refreshed_at: None,
             created_at: now,
             updated_at: now,
         }
     }
 }
+
+/// A token matching an admin's search, as offered for disambiguation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TokenCandidate {
+    pub type_hash: String,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimal: Option<String>,
+    pub holders: Option<u64>,
+}
+
+impl TokenCandidate {
+    pub fn label(&self) -> String {
+        self.symbol
+            .clone()
+            .filter(|symbol| !symbol.is_empty())
+            .or(self.name.clone())
+            .unwrap_or("Unknown".to_owned())
+    }
+
+    fn matches_exactly(&self, query: &str) -> bool {
+        [&self.symbol, &self.name]
+            .into_iter()
+            .flatten()
+            .any(|value| value.eq_ignore_ascii_case(query))
+    }
+
+    /// Exact symbol or name matches first, then the most held
+    pub fn sort(candidates: &mut [TokenCandidate], query: &str) {
+        candidates.sort_by_key(|candidate| {
+            (
+                !candidate.matches_exactly(query),
+                std::cmp::Reverse(candidate.holders.unwrap_or(0)),
+            )
+        });
+    }
+}
+
+impl From<&Token> for TokenCandidate {
+    fn from(token: &Token) -> Self {
+        TokenCandidate {
+            type_hash: token.type_hash.clone(),
+            name: token.name.clone(),
+            symbol: token.symbol.clone(),
+            decimal: token.decimal.clone(),
+            holders: None,
+        }
+    }
+}
@@ -0,0 +1,432 @@
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Display, Formatter},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use ckb_jsonrpc_types::{JsonBytes, Script, ScriptHashType};
+use ckb_sdk::{
+    rpc::ckb_indexer::{Order, ScriptType, SearchKey, SearchKeyFilter, SearchMode},
+    Address,
+};
+use ckb_types::{packed, prelude::*, H256};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value};
+
+use crate::{
+    config::{self, NETWORK_CONFIG},
+    libs::molecule,
+    models::{balance::CachedBalance, token::TOKEN_TYPE_SPORE},
+    repositories::{
+        balance_cache::BalanceCacheDao,
+        btc::{get_rgbpp_balances, merge_balances},
+        ckb::{get_explorer_balances, get_indexer_client, get_spore_code_hash, get_xudt_code_hash},
+        db::DB_POOL,
+        token::TokenDao,
+    },
+    serialize::error::AppError,
+};
+
+pub const BALANCE_PROVIDER_EXPLORER: &str = "explorer";
+pub const BALANCE_PROVIDER_RPC: &str = "rpc";
+pub const BALANCE_PROVIDER_FIXTURE: &str = "fixture";
+
+pub const BALANCE_CACHE_POSTGRES: &str = "postgres";
+pub const BALANCE_CACHE_MEMORY: &str = "memory";
+pub const BALANCE_CACHE_NONE: &str = "none";
+pub const DEFAULT_BALANCE_CACHE_TTL_SECS: i64 = 300;
+
+pub const RPC_CELLS_PAGE_SIZE: u32 = 100;
+pub const RPC_CELLS_MAX_PAGES: usize = 10;
+
+/// The provider could not tell what the address holds. Unlike an empty balance, this must never
+/// be read as the member holding nothing.
+#[derive(Debug, Clone)]
+pub struct BalanceError(pub String);
+
+impl Display for BalanceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<AppError> for BalanceError {
+    fn from(error: AppError) -> Self {
+        BalanceError(error.to_string())
+    }
+}
+
+pub type BalanceResult = Result<Value, BalanceError>;
+
+/// Source of an address's balances, keyed "CKB" or by token type hash (the cluster type hash
+/// for spores), in whole units
+#[async_trait]
+pub trait BalanceProvider: Send + Sync + Debug {
+    async fn get_balances(&self, address: String) -> BalanceResult;
+}
+
+/// Provider selected by `balance_provider` in config, the explorer API by default
+pub static BALANCE_PROVIDER: Lazy<Arc<dyn BalanceProvider>> = Lazy::new(|| {
+    let provider = config::CONFIG
+        .get::<String>("balance_provider")
+        .unwrap_or(BALANCE_PROVIDER_EXPLORER.to_owned());
+    match provider.as_str() {
+        BALANCE_PROVIDER_RPC => Arc::new(RpcBalanceProvider::new(TokenDao::new(DB_POOL.clone()))),
+        BALANCE_PROVIDER_FIXTURE => Arc::new(
+            config::CONFIG
+                .get::<String>("balance_fixtures")
+                .map(|path| FixtureBalanceProvider::from_file(&path))
+                .unwrap_or_else(|_| {
+                    FixtureBalanceProvider::unavailable("balance_fixtures is not configured")
+                }),
+        ),
+        _ => Arc::new(ExplorerBalanceProvider),
+    }
+});
+
+/// Store of fetched balances keyed by lock script hash. Failed lookups are never cached.
+#[async_trait]
+pub trait BalanceCache: Send + Sync + Debug {
+    async fn get(&self, lock_hash: String) -> Option<CachedBalance>;
+    async fn put(&self, entry: CachedBalance);
+}
+
+/// Cache selected by `balance_cache` in config, kept in process memory by default. Use
+/// `postgres` to share it between the server and the cron.
+pub static BALANCE_CACHE: Lazy<Option<Arc<dyn BalanceCache>>> = Lazy::new(|| {
+    let cache = config::CONFIG
+        .get::<String>("balance_cache")
+        .unwrap_or(BALANCE_CACHE_MEMORY.to_owned());
+    match cache.as_str() {
+        BALANCE_CACHE_NONE => None,
+        BALANCE_CACHE_POSTGRES => Some(Arc::new(BalanceCacheDao::new(DB_POOL.clone()))),
+        _ => Some(Arc::new(MemoryBalanceCache::default())),
+    }
+});
+
+fn balance_cache_ttl() -> Duration {
+    let secs = config::CONFIG
+        .get::<i64>("balance_cache_ttl_secs")
+        .unwrap_or(DEFAULT_BALANCE_CACHE_TTL_SECS);
+    Duration::seconds(secs)
+}
+
+/// Balances of the address, served from the cache while younger than `balance_cache_ttl_secs`
+pub async fn get_balances(address: String) -> BalanceResult {
+    if let Some(cache) = BALANCE_CACHE.as_ref() {
+        if let Some(entry) = cache.get(lock_hash(&address)).await {
+            let is_fresh = Utc::now().naive_utc() - entry.fetched_at < balance_cache_ttl();
+            if let (true, Ok(balances)) = (is_fresh, serde_json::from_str(&entry.balances)) {
+                return Ok(balances);
+            }
+        }
+    }
+
+    refresh_balances(address).await
+}
+
+/// Fetch the address's balances from the provider, bypassing and updating the cache
+pub async fn refresh_balances(address: String) -> BalanceResult {
+    let balances = BALANCE_PROVIDER.get_balances(address.clone()).await?;
+    if let Some(cache) = BALANCE_CACHE.as_ref() {
+        cache
+            .put(CachedBalance {
+                lock_hash: lock_hash(&address),
+                balances: balances.to_string(),
+                fetched_at: Utc::now().naive_utc(),
+            })
+            .await;
+    }
+
+    Ok(balances)
+}
+
+/// Balances of the member's CKB address, plus the RGB++ assets of their BTC address if any.
+/// `refresh` skips the cache, for members who just topped up.
+pub async fn get_member_balances(
+    ckb_address: String,
+    btc_address: Option<String>,
+    refresh: bool,
+) -> BalanceResult {
+    let mut balances = if refresh {
+        refresh_balances(ckb_address).await?
+    } else {
+        get_balances(ckb_address).await?
+    };
+    if let Some(btc_address) = btc_address {
+        merge_balances(
+            &mut balances,
+            &get_rgbpp_balances(btc_address, refresh).await?,
+        );
+    }
+
+    Ok(balances)
+}
+
+/// Cache key of an address: the hash of its lock script, so every encoding of the same lock
+/// shares an entry
+fn lock_hash(address: &str) -> String {
+    match Address::from_str(address) {
+        Ok(address) => {
+            let hash: H256 = packed::Script::from(&address).calc_script_hash().unpack();
+            format!("{:#x}", hash)
+        }
+        Err(_) => address.to_owned(),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryBalanceCache {
+    entries: Mutex<HashMap<String, CachedBalance>>,
+}
+
+#[async_trait]
+impl BalanceCache for MemoryBalanceCache {
+    async fn get(&self, lock_hash: String) -> Option<CachedBalance> {
+        self.entries.lock().unwrap().get(&lock_hash).cloned()
+    }
+
+    async fn put(&self, entry: CachedBalance) {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(entry.lock_hash.clone(), entry);
+    }
+}
+
+#[async_trait]
+impl BalanceCache for BalanceCacheDao {
+    async fn get(&self, lock_hash: String) -> Option<CachedBalance> {
+        self.get_balance(lock_hash).await.unwrap_or(None)
+    }
+
+    async fn put(&self, entry: CachedBalance) {
+        if let Err(err) = self.upsert_balance(&entry).await {
+            println!("Balance cache write failed: {:?}", err);
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ExplorerBalanceProvider;
+
+#[async_trait]
+impl BalanceProvider for ExplorerBalanceProvider {
+    async fn get_balances(&self, address: String) -> BalanceResult {
+        Ok(get_explorer_balances(address).await?)
+    }
+}
+
+/// Reads balances straight from a CKB node's indexer RPC.
+///
+/// Token decimals and spore clusters are resolved through the tokens table, so only tokens
+/// the bot already knows about are reported.
+#[derive(Clone, Debug)]
+pub struct RpcBalanceProvider {
+    token_dao: TokenDao,
+}
+
+impl RpcBalanceProvider {
+    pub fn new(token_dao: TokenDao) -> Self {
+        RpcBalanceProvider { token_dao }
+    }
+}
+
+#[async_trait]
+impl BalanceProvider for RpcBalanceProvider {
+    async fn get_balances(&self, address: String) -> BalanceResult {
+        let mut balance_map: HashMap<String, f64> = HashMap::new();
+        let address = Address::from_str(&address)
+            .map_err(|e| BalanceError(format!("invalid address {}: {}", address, e)))?;
+        let lock_script = Script::from(packed::Script::from(&address));
+        let client = get_indexer_client().await;
+        let xudt_filter =
+            type_script_prefix(&get_xudt_code_hash(), NETWORK_CONFIG.xudt_hash_type.clone())?;
+        let spore_filter = type_script_prefix(
+            &get_spore_code_hash(),
+            NETWORK_CONFIG.spore_hash_type.clone(),
+        )?;
+
+        let holdings = tokio::task::spawn_blocking(move || {
+            let capacity = client
+                .get_cells_capacity(lock_search_key(lock_script.clone(), None))
+                .map_err(|e| BalanceError(e.to_string()))?
+                .map(|cells| cells.capacity.value())
+                .unwrap_or(0);
+
+            let mut xudt_amounts: HashMap<String, u128> = HashMap::new();
+            for cell in get_cells(
+                &client,
+                lock_search_key(lock_script.clone(), Some(xudt_filter)),
+            )? {
+                let (Some(type_script), Some(data)) = (cell.output.type_, cell.output_data) else {
+                    continue;
+                };
+                let type_hash: H256 = packed::Script::from(type_script)
+                    .calc_script_hash()
+                    .unpack();
+                let type_hash = format!("{:#x}", type_hash);
+                *xudt_amounts.entry(type_hash).or_default() += xudt_amount(data.as_bytes());
+            }
+
+            let mut spore_counts: HashMap<String, u64> = HashMap::new();
+            for cell in get_cells(&client, lock_search_key(lock_script, Some(spore_filter)))? {
+                if let Some(cluster_id) = cell
+                    .output_data
+                    .and_then(|data| spore_cluster_id(data.as_bytes()))
+                {
+                    *spore_counts.entry(cluster_id).or_default() += 1;
+                }
+            }
+
+            Ok::<_, BalanceError>((capacity, xudt_amounts, spore_counts))
+        })
+        .await
+        .map_err(|e| BalanceError(e.to_string()))?;
+        let (capacity, xudt_amounts, spore_counts) = holdings?;
+
+        balance_map.insert("CKB".to_owned(), capacity as f64 / 10f64.powi(8));
+        for (type_hash, amount) in xudt_amounts {
+            let Ok(Some(token)) = self.token_dao.get_token(type_hash.clone()).await else {
+                continue;
+            };
+            let decimal = token
+                .decimal
+                .unwrap_or("0".to_owned())
+                .parse::<i32>()
+                .unwrap_or(0);
+            balance_map.insert(type_hash, amount as f64 / 10f64.powi(decimal));
+        }
+        for (cluster_id, count) in spore_counts {
+            if let Ok(Some(token)) = self
+                .token_dao
+                .get_token_by_args(TOKEN_TYPE_SPORE, cluster_id)
+                .await
+            {
+                balance_map.insert(token.type_hash, count as f64);
+            }
+        }
+
+        Ok(json!(balance_map))
+    }
+}
+
+/// Serves fixed balances, for running the bot and its checks without any upstream.
+/// Fixtures that failed to load fail every lookup rather than report empty balances.
+#[derive(Clone, Debug)]
+pub struct FixtureBalanceProvider {
+    balances: Result<HashMap<String, Value>, BalanceError>,
+}
+
+impl FixtureBalanceProvider {
+    pub fn new(balances: HashMap<String, Value>) -> Self {
+        FixtureBalanceProvider {
+            balances: Ok(balances),
+        }
+    }
+
+    pub fn unavailable(reason: &str) -> Self {
+        println!("Balance fixtures unavailable: {}", reason);
+        FixtureBalanceProvider {
+            balances: Err(BalanceError(reason.to_owned())),
+        }
+    }
+
+    /// Load a JSON object mapping each address to its balances
+    pub fn from_file(path: &str) -> Self {
+        let balances = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                serde_json::from_str::<HashMap<String, Value>>(&data).map_err(|e| e.to_string())
+            });
+
+        match balances {
+            Ok(balances) => FixtureBalanceProvider::new(balances),
+            Err(err) => FixtureBalanceProvider::unavailable(&format!(
+                "balance fixtures {} could not be loaded: {}",
+                path, err
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl BalanceProvider for FixtureBalanceProvider {
+    async fn get_balances(&self, address: String) -> BalanceResult {
+        let balances = self.balances.as_ref().map_err(Clone::clone)?;
+        Ok(balances.get(&address).cloned().unwrap_or(json!({})))
+    }
+}
+
+fn lock_search_key(lock_script: Script, type_filter: Option<Script>) -> SearchKey {
+    SearchKey {
+        script: lock_script,
+        script_type: ScriptType::Lock,
+        script_search_mode: Some(SearchMode::Exact),
+        filter: type_filter.map(|script| SearchKeyFilter {
+            script: Some(script),
+            ..Default::default()
+        }),
+        with_data: Some(true),
+        group_by_transaction: None,
+    }
+}
+
+/// Type script matching every cell of the given script code, used as an args prefix filter.
+/// A missing code hash is an error, as searching without the filter would count every cell.
+fn type_script_prefix(code_hash: &str, hash_type: ScriptHashType) -> Result<Script, BalanceError> {
+    let code_hash = H256::from_str(code_hash.trim_start_matches("0x"))
+        .map_err(|_| BalanceError(format!("invalid script code hash {:?}", code_hash)))?;
+    Ok(Script {
+        code_hash,
+        hash_type,
+        args: JsonBytes::default(),
+    })
+}
+
+fn get_cells(
+    client: &ckb_sdk::CkbRpcClient,
+    search_key: SearchKey,
+) -> Result<Vec<ckb_sdk::rpc::ckb_indexer::Cell>, BalanceError> {
+    let mut cells = Vec::new();
+    let mut after = None;
+    for _ in 0..RPC_CELLS_MAX_PAGES {
+        let page = client
+            .get_cells(
+                search_key.clone(),
+                Order::Asc,
+                RPC_CELLS_PAGE_SIZE.into(),
+                after,
+            )
+            .map_err(|e| BalanceError(e.to_string()))?;
+        let is_last_page = page.objects.len() < RPC_CELLS_PAGE_SIZE as usize;
+        cells.extend(page.objects);
+        if is_last_page {
+            return Ok(cells);
+        }
+        after = Some(page.last_cursor);
+    }
+
+    // A partial list would undercount the balance
+    Err(BalanceError(format!(
+        "more than {} cells to count",
+        RPC_CELLS_MAX_PAGES * RPC_CELLS_PAGE_SIZE as usize
+    )))
+}
+
+/// xUDT amounts are the first 16 bytes of the cell data, little endian
+fn xudt_amount(data: &[u8]) -> u128 {
+    data.get(..16)
+        .and_then(|amount| amount.try_into().ok())
+        .map(u128::from_le_bytes)
+        .unwrap_or(0)
+}
+
+/// Cluster id of a spore, read from the `cluster_id` field of its molecule `SporeData` table
+fn spore_cluster_id(data: &[u8]) -> Option<String> {
+    let cluster_id = molecule::table_field(data, 2).and_then(molecule::bytes_opt)?;
+    Some(format!("0x{}", hex::encode(cluster_id)))
+}
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use deadpool_postgres::{Client, Pool, PoolError};
+use tokio_pg_mapper::FromTokioPostgresRow;
+
+use crate::models::balance::CachedBalance;
+
+#[derive(Clone, Debug)]
+pub struct BalanceCacheDao {
+    db: Arc<Pool>,
+}
+
+impl BalanceCacheDao {
+    pub fn new(db: Arc<Pool>) -> Self {
+        BalanceCacheDao { db: db.clone() }
+    }
+
+    pub async fn upsert_balance(&self, entry: &CachedBalance) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "INSERT INTO balance_cache (lock_hash, balances, fetched_at) VALUES ($1, $2, $3) ON CONFLICT (lock_hash) DO UPDATE SET balances=EXCLUDED.balances, fetched_at=EXCLUDED.fetched_at;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client
+            .execute(
+                &stmt,
+                &[&entry.lock_hash, &entry.balances, &entry.fetched_at],
+            )
+            .await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    pub async fn get_balance(&self, lock_hash: String) -> Result<Option<CachedBalance>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM balance_cache WHERE lock_hash=$1;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let row = client.query(&stmt, &[&lock_hash]).await?.pop();
+
+        Ok(row.map(|row| CachedBalance::from_row_ref(&row).unwrap()))
+    }
+}
@@ -0,0 +1,107 @@
+use std::str::FromStr;
+
+use ckb_sdk::{Address, AddressPayload};
+use ckb_types::{bytes::Bytes, core::ScriptHashType, prelude::Pack, H256};
+use serde_json::{json, Value};
+
+use crate::{
+    config::NETWORK_CONFIG,
+    libs::http::{self, HTTP_CLIENT},
+    models::btc::BtcUtxo,
+    repositories::{
+        balance::{get_balances, refresh_balances, BalanceError, BalanceResult},
+        ckb::get_ckb_network,
+    },
+};
+
+/// Upper bound on the UTXOs looked up per address, each one costs an explorer request.
+/// Addresses with more fail the lookup rather than report partial holdings.
+pub const RGBPP_MAX_UTXOS: usize = 50;
+
+pub fn get_btc_network() -> bitcoin::Network {
+    NETWORK_CONFIG
+        .btc_network
+        .parse::<bitcoin::Network>()
+        .unwrap_or(bitcoin::Network::Testnet)
+}
+
+pub fn get_btc_api_url() -> String {
+    NETWORK_CONFIG.btc_api.clone()
+}
+
+pub async fn get_address_utxos(address: String) -> Option<Vec<BtcUtxo>> {
+    let endpoint = format!("{}/address/{}/utxo", get_btc_api_url(), address);
+    let request = HTTP_CLIENT.get(endpoint).build().ok()?;
+    let response = http::execute(request).await.ok()?;
+
+    response.json::<Vec<BtcUtxo>>().await.ok()
+}
+
+/// CKB address of the RGB++ lock bound to a Bitcoin UTXO.
+///
+/// The lock args are the output index (u32 LE) followed by the txid in its internal byte order.
+pub fn get_rgbpp_address(txid: &str, vout: u32) -> Option<String> {
+    let mut txid = hex::decode(txid.trim_start_matches("0x")).ok()?;
+    if txid.len() != 32 {
+        return None;
+    }
+    txid.reverse();
+
+    let mut args = vout.to_le_bytes().to_vec();
+    args.extend(txid);
+
+    let code_hash =
+        H256::from_str(NETWORK_CONFIG.rgbpp_lock_code_hash.trim_start_matches("0x")).ok()?;
+    let payload =
+        AddressPayload::new_full(ScriptHashType::Type, code_hash.pack(), Bytes::from(args));
+
+    Some(Address::new(get_ckb_network(), payload, true).to_string())
+}
+
+/// xUDT and spore holdings bound to the UTXOs of a Bitcoin address, keyed like `get_balances`
+pub async fn get_rgbpp_balances(btc_address: String, refresh: bool) -> BalanceResult {
+    let mut balances = json!({});
+    let utxos = get_address_utxos(btc_address.clone())
+        .await
+        .ok_or_else(|| BalanceError(format!("UTXO lookup failed for {}", btc_address)))?;
+    // Skipping UTXOs would undercount the holdings
+    if utxos.len() > RGBPP_MAX_UTXOS {
+        return Err(BalanceError(format!(
+            "{} has more than {} UTXOs to check",
+            btc_address, RGBPP_MAX_UTXOS
+        )));
+    }
+    for utxo in &utxos {
+        let Some(rgbpp_address) = get_rgbpp_address(&utxo.txid, utxo.vout) else {
+            continue;
+        };
+
+        let mut utxo_balances = if refresh {
+            refresh_balances(rgbpp_address).await?
+        } else {
+            get_balances(rgbpp_address).await?
+        };
+        // Capacity of RGB++ cells is not the member's spendable CKB
+        if let Some(map) = utxo_balances.as_object_mut() {
+            map.remove("CKB");
+        }
+        merge_balances(&mut balances, &utxo_balances);
+    }
+
+    Ok(balances)
+}
+
+/// Add every balance of `other` onto `balances`
+pub fn merge_balances(balances: &mut Value, other: &Value) {
+    let (Some(map), Some(other)) = (balances.as_object_mut(), other.as_object()) else {
+        return;
+    };
+
+    for (token, amount) in other {
+        let current = map.get(token).and_then(Value::as_f64).unwrap_or(0.0);
+        map.insert(
+            token.clone(),
+            json!(current + amount.as_f64().unwrap_or(0.0)),
+        );
+    }
+}
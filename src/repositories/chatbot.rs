@@ -1,4 +1,7 @@
-use crate::config;
+use crate::{
+    config,
+    libs::replay::{self, ReplayMode},
+};
 use reqwest::blocking::{Client, Response};
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
 
@@ -22,15 +25,35 @@ pub fn ask_bot(user_msg: &str, bot_name: &str, user_address: &str) -> String {
         "msg": user_msg
     });
 
-    // Make the HTTP request
-    let client = Client::new();
-    let response: Result<Response, reqwest::Error> =
-        client.post(&url).headers(headers).json(&body).send();
+    // Make the HTTP request, or serve it from a recorded fixture
+    let request_body = body.to_string();
+    let response: Result<(u16, String), String> = match replay::mode() {
+        ReplayMode::Replay => replay::load("POST", &url, Some(request_body.as_bytes()))
+            .map(|fixture| (fixture.status, fixture.body))
+            .ok_or(format!("no fixture recorded for POST {}", url)),
+        mode => {
+            let client = Client::new();
+            let response: Result<Response, reqwest::Error> =
+                client.post(&url).headers(headers).json(&body).send();
+            response
+                .and_then(|res| {
+                    let status = res.status().as_u16();
+                    res.text().map(|text| (status, text))
+                })
+                .map(|(status, text)| {
+                    if mode == ReplayMode::Record {
+                        replay::save("POST", &url, Some(request_body.as_bytes()), status, &text);
+                    }
+                    (status, text)
+                })
+                .map_err(|err| err.to_string())
+        }
+    };
 
     match response {
-        Ok(res) => {
-            if res.status().is_success() {
-                match res.json::<serde_json::Value>() {
+        Ok((status, text)) => {
+            if (200..300).contains(&status) {
+                match serde_json::from_str::<serde_json::Value>(&text) {
                     Ok(data) => data["response"]
                         .as_str()
                         .unwrap_or("No response from bot")
@@ -38,7 +61,7 @@ pub fn ask_bot(user_msg: &str, bot_name: &str, user_address: &str) -> String {
                     Err(_) => "Failed to parse bot response.".to_string(),
                 }
             } else {
-                log::error!("Request failed with status: {}", res.status());
+                log::error!("Request failed with status: {}", status);
                 "Request failed.".to_string()
             }
         }
@@ -1,42 +1,64 @@
 use std::collections::HashMap;
 
 use crate::{
-    config,
-    models::ckb::{AddressResponse, NFTInfo, TokenInfo, TokenResponse},
+    config::{self, NETWORK_CONFIG},
+    libs::http::{self, HTTP_CLIENT},
+    models::ckb::{
+        AddressAttributes, AddressResponse, DisplayCell, NFTInfo, TokenInfo, TokenListResponse,
+        TokenResponse, TransactionAttributes, TransactionData, TransactionsResponse,
+    },
+    repositories::balance::BalanceError,
     serialize::error::AppError,
 };
+use chrono::{DateTime, NaiveDateTime};
 use ckb_sdk::{rpc::CkbRpcClient, NetworkType};
-use reqwest::{header, Client};
+use reqwest::header;
 use serde_json::json;
 
-pub const CKB_TESTNET_EXPLORER_API: &str = "https://testnet-api.explorer.nervos.org/api";
-pub const CKB_MAINNET_EXPLORER_API: &str = "https://mainnet-api.explorer.nervos.org/api";
-pub const CKB_TESTNET_RPC: &str = "https://testnet.ckb.dev/rpc";
-pub const CKB_MAINNET_RPC: &str = "https://mainnet.ckb.dev/rpc";
+pub const ADDRESS_TRANSACTIONS_PAGE_SIZE: usize = 50;
+pub const ADDRESS_TRANSACTIONS_MAX_PAGES: usize = 20;
+pub const XUDT_SEARCH_PAGE_SIZE: usize = 10;
 
 pub fn get_ckb_network() -> NetworkType {
     let network: String = config::get("network");
     match network.as_str() {
         "mainnet" => NetworkType::Mainnet,
+        "devnet" => NetworkType::Dev,
         _ => NetworkType::Testnet,
     }
 }
 
-pub fn get_explorer_api_url(network: NetworkType) -> String {
-    if network == NetworkType::Mainnet {
-        return CKB_MAINNET_EXPLORER_API.to_owned();
-    }
-
-    CKB_TESTNET_EXPLORER_API.to_owned()
+pub fn get_explorer_api_url() -> String {
+    NETWORK_CONFIG.explorer_api.clone()
 }
 
 pub fn get_rpc() -> String {
-    let network = get_ckb_network();
-    if network == NetworkType::Mainnet {
-        return CKB_MAINNET_RPC.to_owned();
-    }
+    NETWORK_CONFIG.rpc.clone()
+}
 
-    CKB_TESTNET_RPC.to_owned()
+pub fn get_indexer_rpc() -> String {
+    NETWORK_CONFIG.indexer.clone()
+}
+
+/// Human-readable part of the network's CKB addresses, e.g. "ckb" or "ckt"
+pub fn get_address_prefix() -> String {
+    NETWORK_CONFIG.address_prefix.clone()
+}
+
+pub fn get_xudt_code_hash() -> String {
+    NETWORK_CONFIG.xudt_code_hash.clone()
+}
+
+pub fn get_spore_code_hash() -> String {
+    NETWORK_CONFIG.spore_code_hash.clone()
+}
+
+pub fn get_cluster_code_hash() -> String {
+    NETWORK_CONFIG.cluster_code_hash.clone()
+}
+
+pub fn get_unique_code_hash() -> String {
+    NETWORK_CONFIG.unique_code_hash.clone()
 }
 
 pub async fn get_ckb_client() -> CkbRpcClient {
@@ -46,10 +68,16 @@ pub async fn get_ckb_client() -> CkbRpcClient {
         .expect("Failed to create CkbRpcClient")
 }
 
+pub async fn get_indexer_client() -> CkbRpcClient {
+    let indexer_url: String = get_indexer_rpc();
+    tokio::task::spawn_blocking(move || CkbRpcClient::new(&indexer_url))
+        .await
+        .expect("Failed to create CkbRpcClient")
+}
+
 pub async fn get_xudt_info(type_hash: String) -> Option<TokenInfo> {
-    let network = get_ckb_network();
     let path = &format!("/v1/xudts/{}", type_hash);
-    if let Ok(info) = proxy_request("GET", network, path, None).await {
+    if let Ok(info) = proxy_request("GET", path, None).await {
         if let Ok(token_res) = serde_json::from_value::<TokenResponse>(info) {
             return Some(token_res.data.attributes);
         }
@@ -58,10 +86,31 @@ pub async fn get_xudt_info(type_hash: String) -> Option<TokenInfo> {
     None
 }
 
+/// xUDTs listed by the explorer under the given symbol
+pub async fn search_xudts(symbol: String) -> Vec<TokenInfo> {
+    let mut query = reqwest::Url::parse("http://localhost/").expect("static url");
+    query
+        .query_pairs_mut()
+        .append_pair("symbol", &symbol)
+        .append_pair("page", "1")
+        .append_pair("page_size", &XUDT_SEARCH_PAGE_SIZE.to_string());
+    let path = &format!("/v1/xudts?{}", query.query().unwrap_or_default());
+    if let Ok(info) = proxy_request("GET", path, None).await {
+        if let Ok(token_res) = serde_json::from_value::<TokenListResponse>(info) {
+            return token_res
+                .data
+                .into_iter()
+                .map(|token| token.attributes)
+                .collect();
+        }
+    }
+
+    vec![]
+}
+
 pub async fn get_collection_info(type_hash: String) -> Option<NFTInfo> {
-    let network = get_ckb_network();
     let path = &format!("/v2/nft/collections/{}", type_hash);
-    if let Ok(info) = proxy_request("GET", network, path, None).await {
+    if let Ok(info) = proxy_request("GET", path, None).await {
         if let Ok(nft_info) = serde_json::from_value::<NFTInfo>(info) {
             return Some(nft_info);
         }
@@ -70,86 +119,248 @@ pub async fn get_collection_info(type_hash: String) -> Option<NFTInfo> {
     None
 }
 
-pub async fn get_balances(address: String) -> serde_json::Value {
-    let network = get_ckb_network();
+/// Balances of the address as reported by the explorer API.
+///
+/// Addresses the explorer has never seen have no balances, any other failure is an error.
+pub async fn get_explorer_balances(address: String) -> Result<serde_json::Value, AppError> {
     let path = &format!("/v1/addresses/{}", address);
     let mut balance_map: HashMap<String, f64> = HashMap::new();
-    match proxy_request("GET", network, path, None).await {
-        Ok(info) => match serde_json::from_value::<AddressResponse>(info) {
-            Ok(address_response) => {
-                println!("address_response {:?}", address_response);
-                if let Some(address_data) = address_response.data.first() {
-                    println!("address_data {:?}", address_data);
-                    let attributes = &address_data.attributes;
-                    let balance = attributes
-                        .balance
-                        .clone()
-                        .unwrap_or("0".to_owned())
-                        .parse::<f64>()
-                        .unwrap_or(0.0);
-                    let balance_occupied: f64 = attributes
-                        .live_cells_count
-                        .clone()
-                        .unwrap_or("0".to_owned())
-                        .parse::<f64>()
-                        .unwrap_or(0.0);
-                    balance_map.insert(
-                        "CKB".to_string(),
-                        (balance - balance_occupied) / 10f64.powi(8),
-                    );
-
-                    for udt in &attributes.udt_accounts {
-                        if udt.udt_type.clone().unwrap_or("".to_owned()) == "spore_cell" {
-                            if let Some(collection) = &udt.collection {
-                                if collection.type_hash.is_some() {
-                                    balance_map.insert(collection.type_hash.clone().unwrap(), 1.0);
-                                }
-                            }
-                        } else {
-                            let amount = udt
-                                .amount
-                                .clone()
-                                .unwrap_or("0".to_owned())
-                                .parse::<f64>()
-                                .unwrap_or(0.0);
-                            let decimal = udt
-                                .decimal
-                                .clone()
-                                .unwrap_or("1".to_owned())
-                                .parse::<u32>()
-                                .unwrap_or(1);
-
-                            let balance = if decimal > 0 {
-                                amount / 10f64.powi(decimal as i32)
-                            } else {
-                                amount
-                            };
-
-                            if udt.type_hash.is_some() {
-                                balance_map.insert(udt.type_hash.clone().unwrap(), balance);
-                            }
-                        }
+    let info = proxy_request("GET", path, None).await?;
+    if is_not_found(&info) {
+        return Ok(json!(balance_map));
+    }
+
+    let address_response = serde_json::from_value::<AddressResponse>(info).map_err(|e| {
+        AppError::new(502)
+            .cause(e)
+            .message("parse address response failed")
+    })?;
+    if let Some(address_data) = address_response.data.first() {
+        let attributes = &address_data.attributes;
+        let balance = attributes
+            .balance
+            .clone()
+            .unwrap_or("0".to_owned())
+            .parse::<f64>()
+            .unwrap_or(0.0);
+        let balance_occupied: f64 = attributes
+            .live_cells_count
+            .clone()
+            .unwrap_or("0".to_owned())
+            .parse::<f64>()
+            .unwrap_or(0.0);
+        balance_map.insert(
+            "CKB".to_string(),
+            (balance - balance_occupied) / 10f64.powi(8),
+        );
+
+        for udt in &attributes.udt_accounts {
+            if udt.udt_type.clone().unwrap_or("".to_owned()) == "spore_cell" {
+                if let Some(collection) = &udt.collection {
+                    if collection.type_hash.is_some() {
+                        balance_map.insert(collection.type_hash.clone().unwrap(), 1.0);
                     }
                 }
+            } else {
+                let amount = udt
+                    .amount
+                    .clone()
+                    .unwrap_or("0".to_owned())
+                    .parse::<f64>()
+                    .unwrap_or(0.0);
+                let decimal = udt
+                    .decimal
+                    .clone()
+                    .unwrap_or("1".to_owned())
+                    .parse::<u32>()
+                    .unwrap_or(1);
+
+                let balance = if decimal > 0 {
+                    amount / 10f64.powi(decimal as i32)
+                } else {
+                    amount
+                };
+
+                if udt.type_hash.is_some() {
+                    balance_map.insert(udt.type_hash.clone().unwrap(), balance);
+                }
             }
-            Err(err) => println!("Parse AddressResponse {:?}", err),
-        },
-        Err(err) => {
-            println!("Call API Error: {:?}", err)
         }
     }
-    json!(balance_map)
+
+    Ok(json!(balance_map))
+}
+
+/// Whether the explorer answered with a 404 error document
+fn is_not_found(info: &serde_json::Value) -> bool {
+    info.get("errors")
+        .and_then(|errors| errors.get(0))
+        .and_then(|error| error.get("status"))
+        .map(|status| status.to_string().trim_matches('"') == "404")
+        .unwrap_or(false)
+}
+
+/// Attributes of the address, `None` if the explorer does not know it
+pub async fn get_address_attributes(
+    address: String,
+) -> Result<Option<AddressAttributes>, BalanceError> {
+    let path = &format!("/v1/addresses/{}", address);
+    let info = proxy_request("GET", path, None).await?;
+    if is_not_found(&info) {
+        return Ok(None);
+    }
+
+    let address_res = serde_json::from_value::<AddressResponse>(info)
+        .map_err(|e| BalanceError(format!("invalid address response: {}", e)))?;
+    Ok(address_res
+        .data
+        .into_iter()
+        .next()
+        .map(|data| data.attributes))
+}
+
+/// Time of the address's first transaction, `None` if it has none
+pub async fn get_first_transaction_time(
+    address: String,
+) -> Result<Option<NaiveDateTime>, BalanceError> {
+    let path = &format!(
+        "/v1/address_transactions/{}?page=1&page_size=1&sort=time.asc",
+        address
+    );
+    let info = proxy_request("GET", path, None).await?;
+    if is_not_found(&info) {
+        return Ok(None);
+    }
+
+    let txs_res = serde_json::from_value::<TransactionsResponse>(info)
+        .map_err(|e| BalanceError(format!("invalid transactions response: {}", e)))?;
+    let Some(tx) = txs_res.data.first() else {
+        return Ok(None);
+    };
+    let timestamp = tx
+        .attributes
+        .block_timestamp
+        .clone()
+        .and_then(|timestamp| timestamp.parse::<i64>().ok())
+        .ok_or_else(|| BalanceError("transaction without block timestamp".to_owned()))?;
+
+    Ok(DateTime::from_timestamp_millis(timestamp).map(|time| time.naive_utc()))
+}
+
+pub async fn get_address_transactions(
+    address: String,
+    page: usize,
+    page_size: usize,
+) -> Option<Vec<TransactionData>> {
+    let path = &format!(
+        "/v1/address_transactions/{}?page={}&page_size={}&sort=time.desc",
+        address, page, page_size
+    );
+    if let Ok(info) = proxy_request("GET", path, None).await {
+        if let Ok(txs_res) = serde_json::from_value::<TransactionsResponse>(info) {
+            return Some(txs_res.data);
+        }
+    }
+
+    None
+}
+
+/// Lowest balance of `token` ("CKB" or a type hash) that `address` held at any point since
+/// `since`, found by walking its transaction history backwards from the `current` balance.
+///
+/// Fails when the history could not be fetched, or is too long to page through.
+pub async fn get_min_balance_since(
+    address: String,
+    token: String,
+    current: f64,
+    since: NaiveDateTime,
+) -> Result<f64, BalanceError> {
+    let since_ms = since.and_utc().timestamp_millis();
+    let mut balance = current;
+    let mut min_balance = current;
+
+    for page in 1..=ADDRESS_TRANSACTIONS_MAX_PAGES {
+        let txs = get_address_transactions(address.clone(), page, ADDRESS_TRANSACTIONS_PAGE_SIZE)
+            .await
+            .ok_or_else(|| BalanceError(format!("transactions of {} unavailable", address)))?;
+        let is_last_page = txs.len() < ADDRESS_TRANSACTIONS_PAGE_SIZE;
+
+        for tx in txs {
+            let timestamp = tx
+                .attributes
+                .block_timestamp
+                .clone()
+                .unwrap_or("0".to_owned())
+                .parse::<i64>()
+                .unwrap_or(0);
+            if timestamp < since_ms {
+                return Ok(min_balance);
+            }
+
+            balance -= get_transaction_delta(&tx.attributes, &address, &token);
+            min_balance = min_balance.min(balance);
+        }
+
+        // The address did not exist yet at `since`
+        if is_last_page {
+            return Ok(0.0);
+        }
+    }
+
+    Err(BalanceError(format!(
+        "history of {} is longer than {} transactions",
+        address,
+        ADDRESS_TRANSACTIONS_MAX_PAGES * ADDRESS_TRANSACTIONS_PAGE_SIZE
+    )))
+}
+
+/// Net change of `token` for `address` in a single transaction
+fn get_transaction_delta(tx: &TransactionAttributes, address: &str, token: &str) -> f64 {
+    if token == "CKB" {
+        return tx
+            .income
+            .clone()
+            .unwrap_or("0".to_owned())
+            .parse::<f64>()
+            .unwrap_or(0.0)
+            / 10f64.powi(8);
+    }
+
+    let sum = |cells: &Vec<DisplayCell>| -> f64 {
+        cells
+            .iter()
+            .filter(|cell| cell.address_hash.as_deref() == Some(address))
+            .filter_map(|cell| cell.xudt_info.as_ref().or(cell.udt_info.as_ref()))
+            .filter(|info| info.type_hash.as_deref() == Some(token))
+            .map(|info| {
+                let amount = info
+                    .amount
+                    .clone()
+                    .unwrap_or("0".to_owned())
+                    .parse::<f64>()
+                    .unwrap_or(0.0);
+                let decimal = info
+                    .decimal
+                    .clone()
+                    .unwrap_or("0".to_owned())
+                    .parse::<i32>()
+                    .unwrap_or(0);
+                amount / 10f64.powi(decimal)
+            })
+            .sum()
+    };
+
+    sum(&tx.display_outputs) - sum(&tx.display_inputs)
 }
 
 async fn proxy_request(
     method: &str,
-    network: NetworkType,
     path: &str,
     body: Option<String>,
 ) -> Result<serde_json::Value, AppError> {
-    let ckb_api_url: String = get_explorer_api_url(network);
+    let ckb_api_url: String = get_explorer_api_url();
     let endpoint: String = format!("{}/{}", ckb_api_url, path.to_owned());
-    let client = Client::new();
+    let client = &HTTP_CLIENT;
     let mut request_builder = match method {
         "GET" => client.get(endpoint),
         "POST" => client.post(endpoint),
@@ -159,17 +370,16 @@ async fn proxy_request(
 
     request_builder = request_builder
         .header(header::ACCEPT, "application/vnd.api+json")
-        .header(header::CONTENT_TYPE, "application/vnd.api+json")
-        .header(header::USER_AGENT, "curl/7.68.0");
+        .header(header::CONTENT_TYPE, "application/vnd.api+json");
 
     if let Some(body) = body {
         request_builder = request_builder.body(body);
     }
 
-    let response = request_builder
-        .send()
-        .await
+    let request = request_builder
+        .build()
         .map_err(|error| AppError::new(500).message(&error.to_string()))?;
+    let response = http::execute(request).await?;
 
     let result: serde_json::Value = response
         .json()
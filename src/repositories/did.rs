@@ -0,0 +1,75 @@
+use serde_json::json;
+
+use crate::{
+    config,
+    libs::http::{self, HTTP_CLIENT},
+    models::did::DidAccountListResponse,
+    repositories::balance::BalanceError,
+};
+
+pub const DID_INDEXER_API: &str = "https://indexer-v1.did.id";
+/// SLIP-44 coin type of CKB, the chain the member's lock script lives on
+pub const DID_COIN_TYPE_CKB: &str = "309";
+
+pub fn get_did_indexer_url() -> String {
+    config::CONFIG
+        .get::<String>("did_indexer_url")
+        .unwrap_or(DID_INDEXER_API.to_owned())
+}
+
+/// `.bit` accounts whose DID cells are owned by the lock script of `address`
+pub async fn get_did_accounts(address: String) -> Result<Vec<String>, BalanceError> {
+    let endpoint = format!("{}/v1/account/list", get_did_indexer_url());
+    let body = json!({
+        "type": "blockchain",
+        "key_info": {
+            "coin_type": DID_COIN_TYPE_CKB,
+            "key": address,
+        },
+    });
+
+    let request = HTTP_CLIENT
+        .post(endpoint)
+        .json(&body)
+        .build()
+        .map_err(|e| BalanceError(e.to_string()))?;
+    let response = http::execute(request).await?;
+    let accounts = response
+        .json::<DidAccountListResponse>()
+        .await
+        .map_err(|e| BalanceError(e.to_string()))?;
+    if accounts.errno != 0 {
+        return Err(BalanceError(format!(
+            "DID indexer error: {:?}",
+            accounts.errmsg
+        )));
+    }
+
+    Ok(accounts
+        .data
+        .map(|data| data.account_list)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|account| account.account)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "ckt1qzda0cr08m85hc8jlnfp3zer7xulejywt49kt2rr0vthywaa50xwsqvglkprurm00l7hrs3rfqmmzyy3ll7djdsujdm6z";
+
+    /// Served from the recorded response in `fixtures/http`, see `libs::replay`
+    #[tokio::test]
+    async fn accounts_from_replayed_response() {
+        std::env::set_var("APP_HTTP_REPLAY_MODE", "replay");
+        std::env::set_var("APP_HTTP_FIXTURES_DIR", "fixtures/http");
+
+        let accounts = get_did_accounts(ADDRESS.to_owned()).await.unwrap();
+        assert_eq!(accounts, vec!["alice.bit", "bob.alice.bit"]);
+
+        // Requests without a fixture fail rather than reach the network
+        assert!(get_did_accounts("ckt1unrecorded".to_owned()).await.is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use crate::{config, repositories::balance::BalanceError};
+use ethers::prelude::*;
+
+abigen!(
+    Erc20,
+    r#"[
+        function balanceOf(address owner) external view returns (uint256)
+        function decimals() external view returns (uint8)
+    ]"#
+);
+
+abigen!(
+    Erc721,
+    r#"[
+        function balanceOf(address owner) external view returns (uint256)
+    ]"#
+);
+
+/// JSON-RPC endpoint configured for the chain under `[evm_rpc]`
+pub fn get_evm_rpc(chain_id: i64) -> Option<String> {
+    config::CONFIG
+        .get::<HashMap<String, String>>("evm_rpc")
+        .ok()?
+        .remove(&chain_id.to_string())
+}
+
+fn get_evm_provider(chain_id: i64) -> Result<Provider<Http>, BalanceError> {
+    let rpc_url = get_evm_rpc(chain_id)
+        .ok_or_else(|| BalanceError(format!("no RPC configured for chain {}", chain_id)))?;
+    Provider::<Http>::try_from(rpc_url).map_err(|e| BalanceError(e.to_string()))
+}
+
+fn parse_address(address: &str) -> Result<Address, BalanceError> {
+    address
+        .parse::<Address>()
+        .map_err(|e| BalanceError(format!("invalid EVM address {}: {}", address, e)))
+}
+
+/// ERC-20 balance of `owner`, in whole tokens
+pub async fn get_erc20_balance(
+    chain_id: i64,
+    contract: String,
+    owner: String,
+) -> Result<f64, BalanceError> {
+    let provider = get_evm_provider(chain_id)?;
+    let token = Erc20::new(parse_address(&contract)?, provider.into());
+    let owner = parse_address(&owner)?;
+
+    let balance = token
+        .balance_of(owner)
+        .call()
+        .await
+        .map_err(|e| BalanceError(e.to_string()))?;
+    let decimals = token
+        .decimals()
+        .call()
+        .await
+        .map_err(|e| BalanceError(e.to_string()))?;
+    let amount = ethers::utils::format_units(balance, decimals as u32)
+        .map_err(|e| BalanceError(e.to_string()))?;
+
+    amount
+        .parse::<f64>()
+        .map_err(|e| BalanceError(e.to_string()))
+}
+
+/// Number of tokens of the ERC-721 collection owned by `owner`
+pub async fn get_erc721_balance(
+    chain_id: i64,
+    contract: String,
+    owner: String,
+) -> Result<u64, BalanceError> {
+    let provider = get_evm_provider(chain_id)?;
+    let collection = Erc721::new(parse_address(&contract)?, provider.into());
+    let owner = parse_address(&owner)?;
+
+    let balance = collection
+        .balance_of(owner)
+        .call()
+        .await
+        .map_err(|e| BalanceError(e.to_string()))?;
+
+    Ok(balance.low_u64())
+}
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use deadpool_postgres::{Client, Pool, PoolError};
+use tokio_pg_mapper::FromTokioPostgresRow;
+
+use crate::models::exemption::{
+    GroupExemption, EXEMPTION_KIND_CKB_ADDRESS, EXEMPTION_KIND_TELEGRAM_ID, EXEMPTION_KIND_USERNAME,
+};
+
+#[derive(Clone, Debug)]
+pub struct ExemptionDao {
+    db: Arc<Pool>,
+}
+
+impl ExemptionDao {
+    pub fn new(db: Arc<Pool>) -> Self {
+        ExemptionDao { db: db.clone() }
+    }
+
+    pub async fn add_exemption(
+        &self,
+        exemption: GroupExemption,
+    ) -> Result<GroupExemption, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt =
+            "INSERT INTO tg_group_exemptions (chat_id, kind, value) VALUES ($1, $2, $3) ON CONFLICT (chat_id, kind, value) DO NOTHING ;";
+        let stmt = client.prepare(_stmt).await?;
+
+        client
+            .execute(
+                &stmt,
+                &[&exemption.chat_id, &exemption.kind, &exemption.value],
+            )
+            .await?;
+
+        Ok(exemption)
+    }
+
+    pub async fn delete_exemption(&self, exemption: &GroupExemption) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "DELETE FROM tg_group_exemptions WHERE chat_id=$1 AND kind=$2 AND value=$3;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client
+            .execute(
+                &stmt,
+                &[&exemption.chat_id, &exemption.kind, &exemption.value],
+            )
+            .await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    pub async fn get_exemptions_by_group(
+        &self,
+        chat_id: String,
+    ) -> Result<Vec<GroupExemption>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tg_group_exemptions WHERE chat_id=$1 ORDER BY kind, value;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(&stmt, &[&chat_id])
+            .await?
+            .iter()
+            .map(|row| GroupExemption::from_row_ref(row).unwrap())
+            .collect::<Vec<GroupExemption>>();
+        Ok(rows)
+    }
+
+    /// Whether the member matches any of the group's exemptions by id, username or address
+    pub async fn is_exempt(
+        &self,
+        chat_id: String,
+        user_id: i64,
+        user_name: Option<String>,
+        ckb_address: Option<String>,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT 1 FROM tg_group_exemptions WHERE chat_id=$1 AND ((kind=$2 AND value=$3) OR (kind=$4 AND value=$5) OR (kind=$6 AND value=$7)) LIMIT 1;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(
+                &stmt,
+                &[
+                    &chat_id,
+                    &EXEMPTION_KIND_TELEGRAM_ID,
+                    &user_id.to_string(),
+                    &EXEMPTION_KIND_USERNAME,
+                    &user_name.map(|name| name.to_lowercase()),
+                    &EXEMPTION_KIND_CKB_ADDRESS,
+                    &ckb_address,
+                ],
+            )
+            .await?;
+
+        Ok(!rows.is_empty())
+    }
+}
@@ -0,0 +1,105 @@
+use std::str::FromStr;
+
+use ckb_jsonrpc_types::{Either, Script};
+use ckb_sdk::rpc::ckb_indexer::{Order, ScriptType, SearchKey, SearchMode};
+use ckb_types::H256;
+
+use crate::{
+    libs::molecule,
+    models::ckb::{ClusterMetadata, XudtMetadata},
+    repositories::ckb::{get_ckb_client, get_indexer_client, get_unique_code_hash},
+};
+
+/// Read an xUDT's token info from chain: the unique cell among the outputs of the first
+/// transaction that issued the token
+pub async fn get_xudt_metadata(type_script: Script) -> Option<XudtMetadata> {
+    let unique_code_hash = H256::from_str(get_unique_code_hash().trim_start_matches("0x")).ok()?;
+    let indexer = get_indexer_client().await;
+    let client = get_ckb_client().await;
+
+    tokio::task::spawn_blocking(move || {
+        let search_key = type_search_key(type_script);
+        let first_tx = indexer
+            .get_transactions(search_key, Order::Asc, 1.into(), None)
+            .ok()?
+            .objects
+            .into_iter()
+            .next()?;
+        let tx = client
+            .get_transaction(first_tx.tx_hash())
+            .ok()??
+            .transaction?;
+        let Either::Left(tx) = tx.inner else {
+            return None;
+        };
+
+        tx.inner
+            .outputs
+            .iter()
+            .zip(tx.inner.outputs_data.iter())
+            .filter(|(output, _)| {
+                output
+                    .type_
+                    .as_ref()
+                    .is_some_and(|script| script.code_hash == unique_code_hash)
+            })
+            .find_map(|(_, data)| parse_token_info(data.as_bytes()))
+    })
+    .await
+    .ok()?
+}
+
+/// Read a spore cluster's name and description from its live cluster cell
+pub async fn get_cluster_metadata(type_script: Script) -> Option<ClusterMetadata> {
+    let indexer = get_indexer_client().await;
+
+    tokio::task::spawn_blocking(move || {
+        let cell = indexer
+            .get_cells(type_search_key(type_script), Order::Desc, 1.into(), None)
+            .ok()?
+            .objects
+            .into_iter()
+            .next()?;
+        parse_cluster_data(cell.output_data?.as_bytes())
+    })
+    .await
+    .ok()?
+}
+
+fn type_search_key(type_script: Script) -> SearchKey {
+    SearchKey {
+        script: type_script,
+        script_type: ScriptType::Type,
+        script_search_mode: Some(SearchMode::Exact),
+        filter: None,
+        with_data: Some(true),
+        group_by_transaction: None,
+    }
+}
+
+/// Token info cells hold the decimals as one byte, then the name and the symbol, each
+/// prefixed by its length in one byte
+fn parse_token_info(data: &[u8]) -> Option<XudtMetadata> {
+    let decimal = *data.first()?;
+    let name_len = *data.get(1)? as usize;
+    let name = data.get(2..2 + name_len)?;
+    let symbol_len = *data.get(2 + name_len)? as usize;
+    let symbol = data.get(3 + name_len..3 + name_len + symbol_len)?;
+
+    Some(XudtMetadata {
+        decimal,
+        name: String::from_utf8_lossy(name).into_owned(),
+        symbol: String::from_utf8_lossy(symbol).into_owned(),
+    })
+}
+
+/// `ClusterData` and `ClusterDataV2` both start with the `name` and `description` bytes
+fn parse_cluster_data(data: &[u8]) -> Option<ClusterMetadata> {
+    let name = molecule::table_field(data, 0).and_then(molecule::bytes)?;
+    let description = molecule::table_field(data, 1).and_then(molecule::bytes)?;
+
+    Some(ClusterMetadata {
+        name: String::from_utf8_lossy(name).into_owned(),
+        description: String::from_utf8_lossy(description).into_owned(),
+    })
+}
@@ -1,6 +1,15 @@
+pub mod balance;
+pub mod balance_cache;
+pub mod btc;
 pub mod chatbot;
 pub mod ckb;
 pub mod db;
+pub mod did;
+pub mod evm;
+pub mod exemption;
 pub mod member;
+pub mod metadata;
+pub mod rule;
 pub mod telegram;
+pub mod tier;
 pub mod token;
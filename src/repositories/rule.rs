@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use deadpool_postgres::{Client, Pool, PoolError};
+use tokio_pg_mapper::FromTokioPostgresRow;
+
+use crate::models::rule::GroupRule;
+
+#[derive(Clone, Debug)]
+pub struct RuleDao {
+    db: Arc<Pool>,
+}
+
+impl RuleDao {
+    pub fn new(db: Arc<Pool>) -> Self {
+        RuleDao { db: db.clone() }
+    }
+
+    pub async fn add_rule(&self, rule: GroupRule) -> Result<GroupRule, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt =
+            "INSERT INTO tg_group_rules (chat_id, rule_type, token_address, min_amount, min_days, merkle_root, chain_id) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING *;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let row = client
+            .query_one(
+                &stmt,
+                &[
+                    &rule.chat_id,
+                    &rule.rule_type,
+                    &rule.token_address,
+                    &rule.min_amount,
+                    &rule.min_days,
+                    &rule.merkle_root,
+                    &rule.chain_id,
+                ],
+            )
+            .await?;
+
+        Ok(GroupRule::from_row_ref(&row).unwrap())
+    }
+
+    pub async fn delete_rule(&self, chat_id: String, id: i32) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "DELETE FROM tg_group_rules WHERE chat_id=$1 AND id=$2;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client.execute(&stmt, &[&chat_id, &id]).await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    /// Replace the group's rules of the list's type with the list, all in one transaction
+    pub async fn replace_address_list(
+        &self,
+        rule: GroupRule,
+        addresses: &[String],
+        leaves: &[String],
+    ) -> Result<GroupRule, PoolError> {
+        let mut client: Client = self.db.get().await?;
+        let tx = client.transaction().await?;
+
+        let _stmt = "DELETE FROM tg_group_rules WHERE chat_id=$1 AND rule_type=$2;";
+        let stmt = tx.prepare(_stmt).await?;
+        tx.execute(&stmt, &[&rule.chat_id, &rule.rule_type]).await?;
+
+        let _stmt =
+            "INSERT INTO tg_group_rules (chat_id, rule_type, merkle_root) VALUES ($1, $2, $3) RETURNING *;";
+        let stmt = tx.prepare(_stmt).await?;
+        let row = tx
+            .query_one(&stmt, &[&rule.chat_id, &rule.rule_type, &rule.merkle_root])
+            .await?;
+        let rule = GroupRule::from_row_ref(&row).unwrap();
+
+        let _stmt = "INSERT INTO tg_group_address_lists (rule_id, address) SELECT $1, UNNEST($2::VARCHAR[]) ON CONFLICT (rule_id, address) DO NOTHING;";
+        let stmt = tx.prepare(_stmt).await?;
+        tx.execute(&stmt, &[&rule.id, &addresses]).await?;
+
+        let _stmt = "INSERT INTO tg_group_address_leaves (rule_id, leaf) SELECT $1, UNNEST($2::VARCHAR[]) ON CONFLICT (rule_id, leaf) DO NOTHING;";
+        let stmt = tx.prepare(_stmt).await?;
+        tx.execute(&stmt, &[&rule.id, &leaves]).await?;
+
+        tx.commit().await?;
+        Ok(rule)
+    }
+
+    pub async fn add_addresses(
+        &self,
+        rule_id: i32,
+        addresses: &[String],
+    ) -> Result<u64, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "INSERT INTO tg_group_address_lists (rule_id, address) SELECT $1, UNNEST($2::VARCHAR[]) ON CONFLICT (rule_id, address) DO NOTHING;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client.execute(&stmt, &[&rule_id, &addresses]).await?;
+
+        Ok(affected_rows)
+    }
+
+    pub async fn is_address_listed(
+        &self,
+        rule_id: i32,
+        address: String,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT 1 FROM tg_group_address_lists WHERE rule_id=$1 AND address=$2;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client.query(&stmt, &[&rule_id, &address]).await?;
+
+        Ok(!rows.is_empty())
+    }
+
+    pub async fn is_leaf_listed(&self, rule_id: i32, leaf: String) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT 1 FROM tg_group_address_leaves WHERE rule_id=$1 AND leaf=$2;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client.query(&stmt, &[&rule_id, &leaf]).await?;
+
+        Ok(!rows.is_empty())
+    }
+
+    pub async fn get_leaves(&self, rule_id: i32) -> Result<Vec<String>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT leaf FROM tg_group_address_leaves WHERE rule_id=$1;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(&stmt, &[&rule_id])
+            .await?
+            .iter()
+            .map(|row| row.get::<_, String>(0))
+            .collect::<Vec<String>>();
+        Ok(rows)
+    }
+
+    pub async fn get_rules_by_group(&self, chat_id: String) -> Result<Vec<GroupRule>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tg_group_rules WHERE chat_id=$1 ORDER BY id;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(&stmt, &[&chat_id])
+            .await?
+            .iter()
+            .map(|row| GroupRule::from_row_ref(row).unwrap())
+            .collect::<Vec<GroupRule>>();
+        Ok(rows)
+    }
+}
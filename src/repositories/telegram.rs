@@ -4,7 +4,10 @@ use chrono::{NaiveDate, NaiveDateTime};
 use deadpool_postgres::{Client, Pool, PoolError};
 use tokio_pg_mapper::FromTokioPostgresRow;
 
-use crate::models::telegram::{TelegramGroup, TelegramGroupAdmin, TelegramGroupJoined};
+use crate::models::telegram::{
+    TelegramGroup, TelegramGroupAdmin, TelegramGroupJoined, MEMBER_STATUS_ACCEPTED,
+    MEMBER_STATUS_LAPSED, MEMBER_STATUS_PENDING,
+};
 
 #[derive(Clone, Debug)]
 pub struct TelegramDao {
@@ -20,7 +23,7 @@ impl TelegramDao {
         let client: Client = self.db.get().await?;
 
         let _stmt =
-            "INSERT INTO tg_groups (chat_id, name, token_address, min_approve_balance, min_approve_age) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (chat_id) DO NOTHING ;";
+            "INSERT INTO tg_groups (chat_id, name, token_address, min_approve_balance, min_approve_age, grace_hours, lapse_action, kyc_minutes, ban_minutes, join_mode) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT (chat_id) DO NOTHING ;";
         let stmt = client.prepare(_stmt).await?;
 
         client
@@ -32,6 +35,11 @@ impl TelegramDao {
                     &group.token_address,
                     &group.min_approve_balance,
                     &group.min_approve_age,
+                    &group.grace_hours,
+                    &group.lapse_action,
+                    &group.kyc_minutes,
+                    &group.ban_minutes,
+                    &group.join_mode,
                 ],
             )
             .await?;
@@ -63,7 +71,7 @@ impl TelegramDao {
         let client: Client = self.db.get().await?;
 
         let _stmt =
-            "INSERT INTO tg_group_joined (chat_id, user_id, user_name, expired ) VALUES ($1, $2, $3, $4) ON CONFLICT (chat_id, user_id) DO NOTHING ;";
+            "INSERT INTO tg_group_joined (chat_id, user_id, user_name, expired, via_request) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (chat_id, user_id) DO NOTHING ;";
         let stmt = client.prepare(_stmt).await?;
 
         client
@@ -74,6 +82,7 @@ impl TelegramDao {
                     &member.user_id,
                     &member.user_name,
                     &member.expired,
+                    &member.via_request,
                 ],
             )
             .await?;
@@ -85,7 +94,7 @@ impl TelegramDao {
         let client: Client = self.db.get().await?;
 
         let _stmt =
-            "UPDATE tg_groups SET token_address=$1, min_approve_balance=$2, min_approve_age=$3 WHERE chat_id=$4;";
+            "UPDATE tg_groups SET token_address=$1, min_approve_balance=$2, min_approve_age=$3, grace_hours=$4, lapse_action=$5, kyc_minutes=$6, ban_minutes=$7, join_mode=$8 WHERE chat_id=$9;";
         let stmt = client.prepare(_stmt).await?;
 
         let affected_rows = client
@@ -95,6 +104,11 @@ impl TelegramDao {
                     &group.token_address,
                     &group.min_approve_balance,
                     &group.min_approve_age,
+                    &group.grace_hours,
+                    &group.lapse_action,
+                    &group.kyc_minutes,
+                    &group.ban_minutes,
+                    &group.join_mode,
                     &group.chat_id,
                 ],
             )
@@ -103,6 +117,100 @@ impl TelegramDao {
         Ok(affected_rows > 0)
     }
 
+    pub async fn update_group_status(
+        &self,
+        chat_id: String,
+        status: i16,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tg_groups SET status=$1 WHERE chat_id=$2;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client.execute(&stmt, &[&status, &chat_id]).await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    pub async fn delete_admin(&self, chat_id: String, user_id: i64) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "DELETE FROM tg_group_admins WHERE chat_id=$1 AND user_id=$2;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client.execute(&stmt, &[&chat_id, &user_id]).await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    /// Make the group the one the admin manages from private chat, if they are its admin
+    pub async fn select_admin_group(
+        &self,
+        chat_id: String,
+        user_id: i64,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tg_group_admins SET selected_at=NOW() WHERE chat_id=$1 AND user_id=$2;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client.execute(&stmt, &[&chat_id, &user_id]).await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    /// Group the admin last picked to manage from private chat
+    pub async fn get_selected_group(
+        &self,
+        user_id: i64,
+    ) -> Result<Option<TelegramGroup>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT g.* FROM tg_groups g JOIN tg_group_admins a ON a.chat_id=g.chat_id \
+                     WHERE a.user_id=$1 AND a.selected_at IS NOT NULL ORDER BY a.selected_at DESC LIMIT 1;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let row = client.query(&stmt, &[&user_id]).await?.pop();
+        Ok(row.map(|row| TelegramGroup::from_row_ref(&row).unwrap()))
+    }
+
+    pub async fn update_member_via_request(
+        &self,
+        chat_id: String,
+        user_id: i64,
+        via_request: bool,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tg_group_joined SET via_request=$1 WHERE chat_id=$2 AND user_id=$3;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client
+            .execute(&stmt, &[&via_request, &chat_id, &user_id])
+            .await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    pub async fn update_member_status(
+        &self,
+        chat_id: String,
+        user_id: i64,
+        status: i16,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt =
+            "UPDATE tg_group_joined SET status=$1, retry_at=NULL WHERE chat_id=$2 AND user_id=$3;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client
+            .execute(&stmt, &[&status, &chat_id, &user_id])
+            .await?;
+
+        Ok(affected_rows > 0)
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn update_member(
         &self,
@@ -138,6 +246,141 @@ impl TelegramDao {
         Ok(affected_rows > 0)
     }
 
+    pub async fn update_member_tier(
+        &self,
+        chat_id: String,
+        user_id: i64,
+        tier_id: Option<i32>,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tg_group_joined SET tier_id=$1 WHERE chat_id=$2 AND user_id=$3";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client
+            .execute(&stmt, &[&tier_id, &chat_id, &user_id])
+            .await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    pub async fn update_member_wallets(
+        &self,
+        chat_id: String,
+        user_id: i64,
+        evm_address: Option<String>,
+        btc_address: Option<String>,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tg_group_joined SET evm_address=$1, btc_address=$2 WHERE chat_id=$3 AND user_id=$4";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client
+            .execute(&stmt, &[&evm_address, &btc_address, &chat_id, &user_id])
+            .await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    pub async fn update_member_check(
+        &self,
+        chat_id: String,
+        user_id: i64,
+        status: i16,
+        balances: String,
+        checked_at: NaiveDateTime,
+        warned_at: Option<NaiveDateTime>,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt =
+            "UPDATE tg_group_joined SET status=$1, balances=$2, checked_at=$3, warned_at=$4 WHERE chat_id=$5 AND user_id=$6";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client
+            .execute(
+                &stmt,
+                &[
+                    &status,
+                    &balances,
+                    &checked_at,
+                    &warned_at,
+                    &chat_id,
+                    &user_id,
+                ],
+            )
+            .await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    /// Count one more failed verification for the member, returning the new total
+    pub async fn record_member_failure(
+        &self,
+        chat_id: String,
+        user_id: i64,
+    ) -> Result<i32, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tg_group_joined SET fail_count=fail_count+1 WHERE chat_id=$1 AND user_id=$2 RETURNING fail_count";
+        let stmt = client.prepare(_stmt).await?;
+
+        let row = client.query(&stmt, &[&chat_id, &user_id]).await?.pop();
+
+        Ok(row.map(|row| row.get::<_, i32>(0)).unwrap_or(1))
+    }
+
+    pub async fn reset_member_failures(
+        &self,
+        chat_id: String,
+        user_id: i64,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tg_group_joined SET fail_count=0 WHERE chat_id=$1 AND user_id=$2";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client.execute(&stmt, &[&chat_id, &user_id]).await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    /// Keep the member pending until `retry_at`, pushing their verification deadline past it
+    pub async fn record_balance_retry(
+        &self,
+        chat_id: String,
+        user_id: i64,
+        retry_at: NaiveDateTime,
+        expired: NaiveDateTime,
+    ) -> Result<i32, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tg_group_joined SET balance_retries=balance_retries+1, retry_at=$1, expired=$2 WHERE chat_id=$3 AND user_id=$4 RETURNING balance_retries";
+        let stmt = client.prepare(_stmt).await?;
+
+        let row = client
+            .query_one(&stmt, &[&retry_at, &expired, &chat_id, &user_id])
+            .await?;
+
+        Ok(row.get(0))
+    }
+
+    pub async fn clear_balance_retry(
+        &self,
+        chat_id: String,
+        user_id: i64,
+    ) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tg_group_joined SET balance_retries=0, retry_at=NULL WHERE chat_id=$1 AND user_id=$2";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client.execute(&stmt, &[&chat_id, &user_id]).await?;
+
+        Ok(affected_rows > 0)
+    }
+
     pub async fn update_status_all_members(
         &self,
         chat_id: String,
@@ -203,6 +446,27 @@ impl TelegramDao {
             .collect::<Vec<TelegramGroup>>());
     }
 
+    /// Groups gating on the token, directly or through a rule or tier
+    pub async fn get_groups_by_token(
+        &self,
+        type_hash: String,
+    ) -> Result<Vec<TelegramGroup>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tg_groups WHERE LOWER(token_address)=$1 \
+            OR chat_id IN (SELECT chat_id FROM tg_group_rules WHERE LOWER(token_address)=$1) \
+            OR chat_id IN (SELECT chat_id FROM tg_group_tiers WHERE LOWER(token_address)=$1);";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(&stmt, &[&type_hash.to_lowercase()])
+            .await?
+            .iter()
+            .map(|row| TelegramGroup::from_row_ref(row).unwrap())
+            .collect::<Vec<TelegramGroup>>();
+        Ok(rows)
+    }
+
     pub async fn get_member(
         &self,
         chat_id: String,
@@ -233,6 +497,85 @@ impl TelegramDao {
         Ok(rows)
     }
 
+    pub async fn get_members_in_tiered_groups(
+        &self,
+        status: i16,
+    ) -> Result<Vec<TelegramGroupJoined>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tg_group_joined WHERE status=$1 AND ckb_address IS NOT NULL AND chat_id IN (SELECT chat_id FROM tg_group_tiers);";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(&stmt, &[&status])
+            .await?
+            .iter()
+            .map(|row| TelegramGroupJoined::from_row_ref(row).unwrap())
+            .collect::<Vec<TelegramGroupJoined>>();
+        Ok(rows)
+    }
+
+    pub async fn get_members_to_reverify(
+        &self,
+        checked_before: NaiveDateTime,
+    ) -> Result<Vec<TelegramGroupJoined>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tg_group_joined WHERE status IN ($1, $2) AND ckb_address IS NOT NULL AND (checked_at IS NULL OR checked_at < $3);";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(
+                &stmt,
+                &[
+                    &MEMBER_STATUS_ACCEPTED,
+                    &MEMBER_STATUS_LAPSED,
+                    &checked_before,
+                ],
+            )
+            .await?
+            .iter()
+            .map(|row| TelegramGroupJoined::from_row_ref(row).unwrap())
+            .collect::<Vec<TelegramGroupJoined>>();
+        Ok(rows)
+    }
+
+    pub async fn get_members_to_retry(
+        &self,
+        now: NaiveDateTime,
+    ) -> Result<Vec<TelegramGroupJoined>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tg_group_joined WHERE status=$1 AND retry_at IS NOT NULL AND retry_at <= $2 AND ckb_address IS NOT NULL AND dob IS NOT NULL;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(&stmt, &[&MEMBER_STATUS_PENDING, &now])
+            .await?
+            .iter()
+            .map(|row| TelegramGroupJoined::from_row_ref(row).unwrap())
+            .collect::<Vec<TelegramGroupJoined>>();
+        Ok(rows)
+    }
+
+    pub async fn get_admins_by_group(
+        &self,
+        chat_id: String,
+    ) -> Result<Vec<TelegramGroupAdmin>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tg_group_admins WHERE chat_id=$1;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(&stmt, &[&chat_id])
+            .await?
+            .iter()
+            .map(|row| TelegramGroupAdmin::from_row_ref(row).unwrap())
+            .collect::<Vec<TelegramGroupAdmin>>();
+        Ok(rows)
+    }
+
     pub async fn get_member_by_group(
         &self,
         group_id: String,
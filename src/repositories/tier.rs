@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use deadpool_postgres::{Client, Pool, PoolError};
+use tokio_pg_mapper::FromTokioPostgresRow;
+
+use crate::models::tier::GroupTier;
+
+#[derive(Clone, Debug)]
+pub struct TierDao {
+    db: Arc<Pool>,
+}
+
+impl TierDao {
+    pub fn new(db: Arc<Pool>) -> Self {
+        TierDao { db: db.clone() }
+    }
+
+    pub async fn add_tier(&self, tier: GroupTier) -> Result<GroupTier, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt =
+            "INSERT INTO tg_group_tiers (chat_id, name, token_address, min_balance, permissions) VALUES ($1, $2, $3, $4, $5) RETURNING *;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let row = client
+            .query_one(
+                &stmt,
+                &[
+                    &tier.chat_id,
+                    &tier.name,
+                    &tier.token_address,
+                    &tier.min_balance,
+                    &tier.permissions,
+                ],
+            )
+            .await?;
+
+        Ok(GroupTier::from_row_ref(&row).unwrap())
+    }
+
+    pub async fn delete_tier(&self, chat_id: String, id: i32) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "DELETE FROM tg_group_tiers WHERE chat_id=$1 AND id=$2;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client.execute(&stmt, &[&chat_id, &id]).await?;
+
+        Ok(affected_rows > 0)
+    }
+
+    pub async fn get_tiers_by_group(&self, chat_id: String) -> Result<Vec<GroupTier>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tg_group_tiers WHERE chat_id=$1 ORDER BY token_address, min_balance DESC, id;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(&stmt, &[&chat_id])
+            .await?
+            .iter()
+            .map(|row| GroupTier::from_row_ref(row).unwrap())
+            .collect::<Vec<GroupTier>>();
+        Ok(rows)
+    }
+}
@@ -1,9 +1,10 @@
 use std::sync::Arc;
 
+use chrono::NaiveDateTime;
 use deadpool_postgres::{Client, Pool, PoolError};
 use tokio_pg_mapper::FromTokioPostgresRow;
 
-use crate::models::token::Token;
+use crate::models::token::{Token, TOKEN_FIELD_DECIMAL, TOKEN_FIELD_NAME, TOKEN_FIELD_SYMBOL};
 
 #[derive(Clone, Debug)]
 pub struct TokenDao {
@@ -15,15 +16,24 @@ impl TokenDao {
         TokenDao { db: db.clone() }
     }
 
-    pub async fn add_token(&self, token: Token) -> Result<Token, PoolError> {
+    /// Insert the token or refresh its metadata, keeping the fields an admin overrode
+    pub async fn upsert_token(&self, token: Token) -> Result<Token, PoolError> {
         let client: Client = self.db.get().await?;
 
-        let _stmt =
-            "INSERT INTO tokens (type_hash, name, symbol, decimal, description, token_type, args, code_hash, hash_type) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) ON CONFLICT (type_hash) DO NOTHING";
+        let _stmt = "INSERT INTO tokens (type_hash, name, symbol, decimal, description, token_type, args, code_hash, hash_type, refreshed_at) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
+            ON CONFLICT (type_hash) DO UPDATE SET \
+            name=CASE WHEN 'name'=ANY(tokens.overridden) THEN tokens.name ELSE EXCLUDED.name END, \
+            symbol=CASE WHEN 'symbol'=ANY(tokens.overridden) THEN tokens.symbol ELSE EXCLUDED.symbol END, \
+            decimal=CASE WHEN 'decimal'=ANY(tokens.overridden) THEN tokens.decimal ELSE EXCLUDED.decimal END, \
+            description=EXCLUDED.description, token_type=EXCLUDED.token_type, args=EXCLUDED.args, \
+            code_hash=EXCLUDED.code_hash, hash_type=EXCLUDED.hash_type, \
+            refreshed_at=EXCLUDED.refreshed_at, updated_at=NOW() AT TIME ZONE 'UTC' \
+            RETURNING *;";
         let stmt = client.prepare(_stmt).await?;
 
-        client
-            .execute(
+        let row = client
+            .query_one(
                 &stmt,
                 &[
                     &token.type_hash,
@@ -35,11 +45,57 @@ impl TokenDao {
                     &token.args,
                     &token.code_hash,
                     &token.hash_type,
+                    &token.refreshed_at,
                 ],
             )
             .await?;
 
-        Ok(token)
+        Ok(Token::from_row_ref(&row).unwrap())
+    }
+
+    /// Set the given metadata fields and mark them as overridden
+    pub async fn override_token(
+        &self,
+        type_hash: String,
+        name: Option<String>,
+        symbol: Option<String>,
+        decimal: Option<String>,
+    ) -> Result<Option<Token>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tokens SET \
+            name=COALESCE($2, name), symbol=COALESCE($3, symbol), decimal=COALESCE($4, decimal), \
+            overridden=ARRAY(SELECT DISTINCT UNNEST(overridden || $5::TEXT[])), \
+            updated_at=NOW() AT TIME ZONE 'UTC' \
+            WHERE type_hash=$1 RETURNING *;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let fields: Vec<&str> = [
+            (TOKEN_FIELD_NAME, name.is_some()),
+            (TOKEN_FIELD_SYMBOL, symbol.is_some()),
+            (TOKEN_FIELD_DECIMAL, decimal.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, is_set)| *is_set)
+        .map(|(field, _)| field)
+        .collect();
+        let row = client
+            .query(&stmt, &[&type_hash, &name, &symbol, &decimal, &fields])
+            .await?
+            .pop();
+
+        Ok(row.map(|row| Token::from_row_ref(&row).unwrap()))
+    }
+
+    pub async fn clear_overrides(&self, type_hash: String) -> Result<bool, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "UPDATE tokens SET overridden='{}' WHERE type_hash=$1;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let affected_rows = client.execute(&stmt, &[&type_hash]).await?;
+
+        Ok(affected_rows > 0)
     }
 
     pub async fn get_token(&self, type_hash: String) -> Result<Option<Token>, PoolError> {
@@ -52,4 +108,59 @@ impl TokenDao {
 
         Ok(row.map(|row| Token::from_row_ref(&row).unwrap()))
     }
+
+    pub async fn get_token_by_args(
+        &self,
+        token_type: i16,
+        args: String,
+    ) -> Result<Option<Token>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tokens WHERE token_type=$1 AND args=$2;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let row = client.query(&stmt, &[&token_type, &args]).await?.pop();
+
+        Ok(row.map(|row| Token::from_row_ref(&row).unwrap()))
+    }
+
+    /// Tokens matching `search` in their type hash, name or symbol, by name
+    pub async fn get_tokens(
+        &self,
+        search: Option<String>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Token>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tokens WHERE $1::VARCHAR IS NULL OR type_hash ILIKE $1 OR name ILIKE $1 OR symbol ILIKE $1 ORDER BY name, type_hash LIMIT $2 OFFSET $3;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let pattern = search.map(|search| format!("%{}%", search));
+        let rows = client
+            .query(&stmt, &[&pattern, &limit, &offset])
+            .await?
+            .iter()
+            .map(|row| Token::from_row_ref(row).unwrap())
+            .collect::<Vec<Token>>();
+        Ok(rows)
+    }
+
+    pub async fn get_tokens_to_refresh(
+        &self,
+        refreshed_before: NaiveDateTime,
+    ) -> Result<Vec<Token>, PoolError> {
+        let client: Client = self.db.get().await?;
+
+        let _stmt = "SELECT * FROM tokens WHERE refreshed_at IS NULL OR refreshed_at < $1;";
+        let stmt = client.prepare(_stmt).await?;
+
+        let rows = client
+            .query(&stmt, &[&refreshed_before])
+            .await?
+            .iter()
+            .map(|row| Token::from_row_ref(row).unwrap())
+            .collect::<Vec<Token>>();
+        Ok(rows)
+    }
 }
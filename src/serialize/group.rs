@@ -0,0 +1,19 @@
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AddressListReq {
+    pub addresses: Vec<String>,
+    #[serde(default)]
+    pub root_only: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AddressProofQuery {
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AddressProofResp {
+    pub merkle_root: String,
+    pub proof: Vec<String>,
+}
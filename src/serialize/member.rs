@@ -8,4 +8,14 @@ pub struct VerifyMemberReq {
     pub signature: String,
     pub dob: NaiveDate,
     pub sign_type: String,
+    #[serde(default)]
+    pub merkle_proof: Option<Vec<String>>,
+    #[serde(default)]
+    pub btc_address: Option<String>,
+    /// Skip cached balances, for members who just topped up
+    #[serde(default)]
+    pub refresh_balances: bool,
+    /// Only verify for this group, as passed to the Mini App
+    #[serde(default)]
+    pub chat_id: Option<String>,
 }
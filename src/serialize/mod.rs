@@ -1,2 +1,4 @@
 pub mod error;
+pub mod group;
 pub mod member;
+pub mod token;
@@ -0,0 +1,42 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::models::{telegram::TelegramGroup, token::Token};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TokenSearchReq {
+    #[serde(default)]
+    pub q: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: i64,
+    #[serde(default)]
+    pub offset: i64,
+}
+
+fn default_limit() -> i64 {
+    20
+}
+
+/// Type script of a token to add to the registry
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TokenScriptReq {
+    pub code_hash: String,
+    pub hash_type: String,
+    pub args: String,
+}
+
+/// Fields left out keep their current value
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TokenOverrideReq {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub symbol: Option<String>,
+    #[serde(default)]
+    pub decimal: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TokenDetailRes {
+    pub token: Token,
+    pub groups: Vec<TelegramGroup>,
+}
@@ -1,34 +1,68 @@
 use crate::{
-    config::{self, MEMBER_BAN_DURATION},
-    libs::signer::{types, verify},
+    config::{self, BALANCE_RETRY_BASE, BALANCE_RETRY_MAX},
+    libs::signer::{
+        btc,
+        types::{self, BTC_ECDSA, EVM_PERSONAL},
+        verify,
+    },
     models::telegram::{
-        TelegramGroup, MEMBER_STATUS_ACCEPTED, MEMBER_STATUS_PENDING, MEMBER_STATUS_REJECT,
+        format_duration, TelegramGroup, TelegramGroupJoined, GROUP_STATUS_ACTIVE,
+        MEMBER_STATUS_ACCEPTED, MEMBER_STATUS_PENDING, MEMBER_STATUS_REJECT,
+    },
+    repositories::{
+        balance::{get_member_balances, BalanceError, BalanceResult},
+        btc::get_btc_network,
+        exemption::ExemptionDao,
+        member::MemberDao,
+        telegram::TelegramDao,
     },
-    repositories::{ckb::get_balances, member::MemberDao, telegram::TelegramDao},
     serialize::{error::AppError, member::VerifyMemberReq},
+    services::{rule::RuleSrv, tier::TierSrv},
 };
 
-use chrono::{Datelike, NaiveDate, Utc};
-use serde_json::Value;
+use chrono::{Datelike, NaiveDate, TimeDelta, Utc};
+use serde_json::json;
 use std::collections::HashMap;
 use teloxide::{
     payloads::BanChatMemberSetters,
     prelude::Requester,
-    types::{ChatPermissions, UserId},
+    types::{ChatId, ChatPermissions, UserId},
     Bot,
 };
 
+/// Wallets a member proved ownership of when verifying
+#[derive(Clone, Debug)]
+pub struct MemberWallets {
+    pub ckb_address: String,
+    pub dob: NaiveDate,
+    pub evm_address: Option<String>,
+    pub btc_address: Option<String>,
+    pub merkle_proof: Option<Vec<String>>,
+}
+
 #[derive(Clone, Debug)]
 pub struct MemberSrv {
     member_dao: MemberDao,
     tele_dao: TelegramDao,
+    rule_srv: RuleSrv,
+    tier_srv: TierSrv,
+    exemption_dao: ExemptionDao,
 }
 
 impl MemberSrv {
-    pub fn new(member_dao: MemberDao, tele_dao: TelegramDao) -> Self {
+    pub fn new(
+        member_dao: MemberDao,
+        tele_dao: TelegramDao,
+        rule_srv: RuleSrv,
+        tier_srv: TierSrv,
+        exemption_dao: ExemptionDao,
+    ) -> Self {
         MemberSrv {
             member_dao: member_dao.clone(),
             tele_dao: tele_dao.clone(),
+            rule_srv: rule_srv.clone(),
+            tier_srv: tier_srv.clone(),
+            exemption_dao: exemption_dao.clone(),
         }
     }
 
@@ -36,156 +70,462 @@ impl MemberSrv {
         let challenge = format!("My tgid: {} - My DoB: {}", req.tgid, req.dob);
         let mut sign_data = types::SignData::from(&req.signature);
         sign_data.ckb_address = Some(req.ckb_address.clone());
+        let evm_address = if sign_data.sign_type.eq_ignore_ascii_case(EVM_PERSONAL) {
+            Some(sign_data.identity.clone())
+        } else {
+            None
+        };
+        // The BTC address is only trusted when it belongs to the signing key
+        let btc_address = match &req.btc_address {
+            Some(address)
+                if sign_data.sign_type.eq_ignore_ascii_case(BTC_ECDSA)
+                    && btc::is_address_of(&sign_data.identity, address, get_btc_network()) =>
+            {
+                Some(address.clone())
+            }
+            _ => None,
+        };
 
         if verify::verify_message(&challenge, sign_data) {
-            self.verify_info(req).await;
+            let wallets = MemberWallets {
+                ckb_address: req.ckb_address,
+                dob: req.dob,
+                evm_address,
+                btc_address,
+                merkle_proof: req.merkle_proof,
+            };
+            self.verify_info(req.tgid, req.chat_id, wallets, req.refresh_balances)
+                .await;
             Ok(())
         } else {
             Err(AppError::new(500).message("Signature not matched"))
         }
     }
 
-    pub async fn verify_info(&self, req: VerifyMemberReq) {
-        let mut groups: HashMap<String, TelegramGroup> = HashMap::new();
+    pub async fn verify_info(
+        &self,
+        tgid: i64,
+        chat_id: Option<String>,
+        wallets: MemberWallets,
+        refresh: bool,
+    ) {
         match self
             .tele_dao
-            .get_group_by_user_id(req.tgid, Some(MEMBER_STATUS_PENDING))
+            .get_group_by_user_id(tgid, Some(MEMBER_STATUS_PENDING))
             .await
         {
             Ok(joined_groups) => {
-                let balances = get_balances(req.ckb_address.clone()).await;
-                let age = self.calc_age(req.dob);
+                let balances = get_member_balances(
+                    wallets.ckb_address.clone(),
+                    wallets.btc_address.clone(),
+                    refresh,
+                )
+                .await;
                 let bot_token: String = config::get("bot_token");
                 let bot = Bot::new(bot_token);
+                let mut groups: HashMap<String, Option<TelegramGroup>> = HashMap::new();
                 for member in joined_groups {
-                    if member.status == MEMBER_STATUS_ACCEPTED {
+                    if member.status == MEMBER_STATUS_ACCEPTED
+                        || chat_id
+                            .as_ref()
+                            .is_some_and(|chat_id| *chat_id != member.chat_id)
+                    {
                         continue;
                     }
 
-                    let group: Option<TelegramGroup> = if let Some(existing_group) =
-                        groups.get(&member.chat_id)
-                    {
-                        Some(existing_group.clone())
-                    } else {
-                        match self.tele_dao.get_group(member.chat_id.clone()).await {
-                            Ok(fetched_group) => {
-                                groups
-                                    .insert(member.chat_id.clone(), fetched_group.clone().unwrap());
-                                fetched_group
-                            }
-                            Err(_) => None,
-                        }
-                    };
-
-                    if group.is_none() {
+                    let Some(group) = self.get_group_cached(&mut groups, &member.chat_id).await
+                    else {
                         println!("Group {} not existed", member.chat_id);
                         continue;
-                    }
+                    };
+
+                    self.check_member(&bot, &group, member, &wallets, &balances)
+                        .await;
+                }
+            }
+            Err(err) => {
+                println!("{:?}", err)
+            }
+        }
+    }
 
-                    let group_unwrap = group.clone().unwrap();
-                    let min_age_approved = group_unwrap.clone().min_approve_age.unwrap_or(0);
-                    let min_balance_approved =
-                        group_unwrap.clone().min_approve_balance.unwrap_or(0) as f64;
+    /// Re-run the checks of pending members whose balance lookup failed earlier
+    pub async fn cron_retry_balance_checks(&self) {
+        let now = Utc::now().naive_utc();
+        let members = match self.tele_dao.get_members_to_retry(now).await {
+            Ok(members) => members,
+            Err(err) => {
+                println!("{:?}", err);
+                return;
+            }
+        };
 
-                    let mut token_address = group_unwrap
-                        .clone()
-                        .token_address
-                        .unwrap_or("CKB".to_owned());
+        let bot_token: String = config::get("bot_token");
+        let bot = Bot::new(bot_token);
+        let mut groups: HashMap<String, Option<TelegramGroup>> = HashMap::new();
+        let mut balances_by_wallets: HashMap<(String, Option<String>), BalanceResult> =
+            HashMap::new();
+        for member in members {
+            let Some(group) = self.get_group_cached(&mut groups, &member.chat_id).await else {
+                continue;
+            };
+            if group.status != GROUP_STATUS_ACTIVE {
+                continue;
+            }
 
-                    if token_address.is_empty() {
-                        token_address = "CKB".to_owned();
-                    }
+            let wallets = MemberWallets {
+                ckb_address: member.ckb_address.clone().unwrap_or_default(),
+                dob: member.dob.unwrap_or_default(),
+                evm_address: member.evm_address.clone(),
+                btc_address: member.btc_address.clone(),
+                merkle_proof: None,
+            };
+            let key = (wallets.ckb_address.clone(), wallets.btc_address.clone());
+            if !balances_by_wallets.contains_key(&key) {
+                let balances = get_member_balances(
+                    wallets.ckb_address.clone(),
+                    wallets.btc_address.clone(),
+                    false,
+                )
+                .await;
+                balances_by_wallets.insert(key.clone(), balances);
+            }
+
+            self.check_member(&bot, &group, member, &wallets, &balances_by_wallets[&key])
+                .await;
+        }
+    }
+
+    async fn get_group_cached(
+        &self,
+        groups: &mut HashMap<String, Option<TelegramGroup>>,
+        chat_id: &String,
+    ) -> Option<TelegramGroup> {
+        if !groups.contains_key(chat_id) {
+            let group = self
+                .tele_dao
+                .get_group(chat_id.clone())
+                .await
+                .unwrap_or(None);
+            groups.insert(chat_id.clone(), group);
+        }
+
+        groups[chat_id].clone()
+    }
 
-                    let balance = balances
-                        .get(token_address)
-                        .and_then(Value::as_f64)
-                        .unwrap_or(0.0);
-
-                    if balance >= min_balance_approved && age >= min_age_approved {
-                        let _ = bot
-                            .restrict_chat_member(
-                                member.clone().chat_id.to_string(),
-                                UserId(member.clone().user_id as u64),
-                                ChatPermissions::all(),
-                            )
-                            .await;
-                        bot.send_message(
-                            member.clone().chat_id.to_string(),
-                            format!(
-                                "🟢 **Verification successful!**\n\
-                                Welcome, **{}** — you now have full access. Enjoy the chat! 🎉",
-                                member.clone().user_name
-                            ),
+    /// Accept or reject a pending member. A failed balance lookup leaves them pending instead.
+    async fn check_member(
+        &self,
+        bot: &Bot,
+        group: &TelegramGroup,
+        member: TelegramGroupJoined,
+        wallets: &MemberWallets,
+        balances: &BalanceResult,
+    ) {
+        let age = self.calc_age(wallets.dob);
+        let min_age_approved = group.min_approve_age.unwrap_or(0);
+
+        let is_exempt = self
+            .exemption_dao
+            .is_exempt(
+                member.chat_id.clone(),
+                member.user_id,
+                Some(member.user_name.clone()),
+                Some(wallets.ckb_address.clone()),
+            )
+            .await
+            .unwrap_or(false);
+
+        let no_balances = json!({});
+        let rejection = if is_exempt {
+            None
+        } else if age < min_age_approved {
+            Some(format!("Under {} years old", min_age_approved))
+        } else {
+            // Address lists are checked without balances, so a failed lookup does not hold
+            // the member back, nor lose the merkle proof they signed with
+            let checked = match balances {
+                Err(err) if !self.rule_srv.has_address_list(group.chat_id.clone()).await => {
+                    Err(err.clone())
+                }
+                _ => {
+                    self.rule_srv
+                        .check_group(
+                            group,
+                            wallets.ckb_address.clone(),
+                            wallets.evm_address.clone(),
+                            balances.as_ref().unwrap_or(&no_balances),
+                            wallets.merkle_proof.as_ref(),
                         )
                         .await
-                        .unwrap();
-
-                        let _ = self
-                            .tele_dao
-                            .update_member(
-                                Some(req.ckb_address.clone()),
-                                Some(req.dob),
-                                member.chat_id,
-                                member.user_id,
-                                member.expired,
-                                MEMBER_STATUS_ACCEPTED,
-                                balances.to_string(),
-                            )
-                            .await;
-                    } else {
-                        let until_date = Utc::now() + MEMBER_BAN_DURATION;
-                        let _ = bot
-                            .ban_chat_member(
-                                member.clone().chat_id.to_string(),
-                                UserId(member.clone().user_id as u64),
-                            )
-                            .until_date(until_date)
-                            .await;
-
-                        let reason = if age < min_age_approved {
-                            format!("Under {} years old", min_age_approved)
-                        } else {
-                            format!(
-                                "Insufficient balance(Min: {} {})",
-                                min_balance_approved.clone(),
-                                group_unwrap
-                                    .clone()
-                                    .token_address
-                                    .unwrap_or("CKB".to_string())
-                            )
-                        };
-
-                        let _ = bot
-                            .send_message(
-                                member.clone().chat_id.to_string(),
-                                format!(
-                                    "🔴 **{}** failed verification and was removed.\n\
-                                    _Reason:_ {}.\n\
-                                    They can rejoin and try again after the 15‑minute cooldown.",
-                                    member.clone().user_name,
-                                    reason
-                                ),
-                            )
-                            .await;
-
-                        let _ = self
-                            .tele_dao
-                            .update_member(
-                                Some(req.ckb_address.clone()),
-                                Some(req.dob),
-                                member.chat_id,
-                                member.user_id,
-                                member.expired,
-                                MEMBER_STATUS_REJECT,
-                                balances.to_string(),
-                            )
-                            .await;
-                    }
+                }
+            };
+            match checked {
+                Ok(result) => result.err(),
+                Err(err) => {
+                    self.defer_member(bot, group, member, wallets, &err).await;
+                    return;
                 }
             }
-            Err(err) => {
-                println!("{:?}", err)
+        };
+
+        let balances = balances.as_ref().unwrap_or(&no_balances);
+        let _ = self
+            .tele_dao
+            .clear_balance_retry(member.chat_id.clone(), member.user_id)
+            .await;
+
+        if rejection.is_none() {
+            let (tier, permissions) = if is_exempt {
+                (None, ChatPermissions::all())
+            } else {
+                self.tier_srv
+                    .resolve_permissions(member.chat_id.clone(), balances)
+                    .await
+            };
+            if member.via_request {
+                // Tier permissions are applied once the approved member joins
+                let _ = bot
+                    .approve_chat_join_request(
+                        member.chat_id.clone(),
+                        UserId(member.user_id as u64),
+                    )
+                    .await;
+                let _ = bot
+                    .send_message(
+                        ChatId(member.user_id),
+                        format!(
+                            "🟢 **Verification successful!**\n\
+                            Your request to join **{}** was approved. Enjoy the chat! 🎉",
+                            group.name
+                        ),
+                    )
+                    .await;
+            } else {
+                let _ = bot
+                    .restrict_chat_member(
+                        member.clone().chat_id.to_string(),
+                        UserId(member.clone().user_id as u64),
+                        permissions,
+                    )
+                    .await;
+                bot.send_message(
+                    member.clone().chat_id.to_string(),
+                    format!(
+                        "🟢 **Verification successful!**\n\
+                        Welcome, **{}** — you now have full access. Enjoy the chat! 🎉",
+                        member.clone().user_name
+                    ),
+                )
+                .await
+                .unwrap();
+                let _ = bot
+                    .send_message(
+                        ChatId(member.user_id),
+                        format!(
+                            "🟢 **Verification successful!**\n\
+                            You now have access to **{}**.",
+                            group.name
+                        ),
+                    )
+                    .await;
             }
+
+            let _ = self
+                .tele_dao
+                .update_member(
+                    Some(wallets.ckb_address.clone()),
+                    Some(wallets.dob),
+                    member.chat_id.clone(),
+                    member.user_id,
+                    member.expired,
+                    MEMBER_STATUS_ACCEPTED,
+                    balances.to_string(),
+                )
+                .await;
+            let _ = self
+                .tele_dao
+                .update_member_tier(
+                    member.chat_id.clone(),
+                    member.user_id,
+                    tier.map(|tier| tier.id),
+                )
+                .await;
+            let _ = self
+                .tele_dao
+                .update_member_wallets(
+                    member.chat_id.clone(),
+                    member.user_id,
+                    wallets.evm_address.clone(),
+                    wallets.btc_address.clone(),
+                )
+                .await;
+            let _ = self
+                .tele_dao
+                .reset_member_failures(member.chat_id, member.user_id)
+                .await;
+        } else {
+            let fail_count = self
+                .tele_dao
+                .record_member_failure(member.chat_id.clone(), member.user_id)
+                .await
+                .unwrap_or(1);
+            let reason = rejection.unwrap_or_default();
+            let ban_duration = group.ban_duration(fail_count);
+            let until_date = Utc::now() + ban_duration;
+            if member.via_request {
+                let _ = bot
+                    .decline_chat_join_request(
+                        member.chat_id.clone(),
+                        UserId(member.user_id as u64),
+                    )
+                    .await;
+                // Banning a non-member keeps them from sending another request until it ends
+                let _ = bot
+                    .ban_chat_member(member.chat_id.clone(), UserId(member.user_id as u64))
+                    .until_date(until_date)
+                    .await;
+                let _ = bot
+                    .send_message(
+                        ChatId(member.user_id),
+                        format!(
+                            "🔴 Your request to join **{}** was declined.\n\
+                            _Reason:_ {}.\n\
+                            You can request again in {}.",
+                            group.name,
+                            reason,
+                            format_duration(ban_duration)
+                        ),
+                    )
+                    .await;
+            } else {
+                let _ = bot
+                    .ban_chat_member(
+                        member.clone().chat_id.to_string(),
+                        UserId(member.clone().user_id as u64),
+                    )
+                    .until_date(until_date)
+                    .await;
+
+                let _ = bot
+                    .send_message(
+                        member.clone().chat_id.to_string(),
+                        format!(
+                            "🔴 **{}** failed verification and was removed.\n\
+                            _Reason:_ {}.\n\
+                            They can rejoin and try again after the {} cooldown.",
+                            member.clone().user_name,
+                            reason,
+                            format_duration(ban_duration)
+                        ),
+                    )
+                    .await;
+                let _ = bot
+                    .send_message(
+                        ChatId(member.user_id),
+                        format!(
+                            "🔴 Verification for **{}** failed.\n\
+                            _Reason:_ {}.\n\
+                            You can rejoin and try again after the {} cooldown.",
+                            group.name,
+                            reason,
+                            format_duration(ban_duration)
+                        ),
+                    )
+                    .await;
+            }
+
+            let _ = self
+                .tele_dao
+                .update_member(
+                    Some(wallets.ckb_address.clone()),
+                    Some(wallets.dob),
+                    member.chat_id,
+                    member.user_id,
+                    member.expired,
+                    MEMBER_STATUS_REJECT,
+                    balances.to_string(),
+                )
+                .await;
+        }
+    }
+
+    /// Keep the member pending and schedule another check, alerting the admins the first time
+    async fn defer_member(
+        &self,
+        bot: &Bot,
+        group: &TelegramGroup,
+        member: TelegramGroupJoined,
+        wallets: &MemberWallets,
+        err: &BalanceError,
+    ) {
+        println!("Balance lookup failed for {}: {}", wallets.ckb_address, err);
+
+        let retry_at = Utc::now().naive_utc() + balance_retry_delay(member.balance_retries);
+        let expired = retry_at + group.kyc_duration();
+        let _ = self
+            .tele_dao
+            .update_member(
+                Some(wallets.ckb_address.clone()),
+                Some(wallets.dob),
+                member.chat_id.clone(),
+                member.user_id,
+                expired,
+                MEMBER_STATUS_PENDING,
+                member.balances.clone().unwrap_or("{}".to_owned()),
+            )
+            .await;
+        let _ = self
+            .tele_dao
+            .update_member_wallets(
+                member.chat_id.clone(),
+                member.user_id,
+                wallets.evm_address.clone(),
+                wallets.btc_address.clone(),
+            )
+            .await;
+        let _ = self
+            .tele_dao
+            .record_balance_retry(member.chat_id.clone(), member.user_id, retry_at, expired)
+            .await;
+
+        if member.balance_retries > 0 {
+            return;
+        }
+
+        // Applicants of join requests are not in the group yet, tell them directly
+        let notice_chat = if member.via_request {
+            ChatId(member.user_id).to_string()
+        } else {
+            member.chat_id.clone()
+        };
+        let _ = bot
+            .send_message(
+                notice_chat,
+                format!(
+                    "⏳ Balances of **{}** could not be checked right now.\n\
+                    Verification will be retried automatically, no need to sign again.",
+                    member.user_name
+                ),
+            )
+            .await;
+
+        let admins = self
+            .tele_dao
+            .get_admins_by_group(member.chat_id.clone())
+            .await
+            .unwrap_or_default();
+        for admin in admins {
+            let _ = bot
+                .send_message(
+                    ChatId(admin.user_id),
+                    format!(
+                        "⚠️ Balance lookup failed while verifying {} in {}: {}\n\
+                        They stay pending and will be checked again automatically.",
+                        member.user_name, group.name, err
+                    ),
+                )
+                .await;
         }
     }
 
@@ -213,3 +553,8 @@ impl MemberSrv {
         age
     }
 }
+
+/// Delay before the next balance lookup, doubling with every failed attempt
+fn balance_retry_delay(retries: i32) -> TimeDelta {
+    (BALANCE_RETRY_BASE * 2i32.pow(retries.clamp(0, 10) as u32)).min(BALANCE_RETRY_MAX)
+}
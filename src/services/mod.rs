@@ -1,2 +1,6 @@
 pub mod member;
+pub mod rule;
+pub mod settings;
 pub mod telegram;
+pub mod tier;
+pub mod token;
@@ -0,0 +1,402 @@
+use chrono::{Duration, Utc};
+use serde_json::Value;
+
+use crate::{
+    libs::merkle::{leaf_hash, leaf_hashes, merkle_proof, merkle_root, verify_proof},
+    models::{
+        rule::{
+            GroupRule, RULE_TYPE_ADDRESS_LIST, RULE_TYPE_DID_ACCOUNT, RULE_TYPE_ERC20_BALANCE,
+            RULE_TYPE_ERC721_OWNERSHIP, RULE_TYPE_HOLDING_PERIOD, RULE_TYPE_MIN_ADDRESS_AGE,
+            RULE_TYPE_MIN_TRANSACTIONS,
+        },
+        telegram::TelegramGroup,
+    },
+    repositories::{
+        balance::BalanceError,
+        ckb::{
+            get_address_attributes, get_address_prefix, get_first_transaction_time,
+            get_min_balance_since,
+        },
+        did::get_did_accounts,
+        evm::{get_erc20_balance, get_erc721_balance},
+        rule::RuleDao,
+    },
+    serialize::error::AppError,
+};
+
+/// Outcome of checking an address: the inner `Err` is why it was rejected, the outer one a
+/// lookup that failed, leaving it unknown whether the address meets the requirement
+pub type CheckResult = Result<Result<(), String>, BalanceError>;
+
+#[derive(Clone, Debug)]
+pub struct RuleSrv {
+    rule_dao: RuleDao,
+}
+
+impl RuleSrv {
+    pub fn new(rule_dao: RuleDao) -> Self {
+        RuleSrv {
+            rule_dao: rule_dao.clone(),
+        }
+    }
+
+    pub async fn add_rule(&self, rule: GroupRule) -> Result<GroupRule, AppError> {
+        self.rule_dao
+            .add_rule(rule)
+            .await
+            .map_err(|e| AppError::new(500).cause(e).message("add rule failed"))
+    }
+
+    pub async fn delete_rule(&self, chat_id: String, id: i32) -> Result<bool, AppError> {
+        self.rule_dao
+            .delete_rule(chat_id, id)
+            .await
+            .map_err(|e| AppError::new(500).cause(e).message("delete rule failed"))
+    }
+
+    pub async fn get_rules(&self, chat_id: String) -> Vec<GroupRule> {
+        self.rule_dao
+            .get_rules_by_group(chat_id)
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Pick the CKB addresses out of an uploaded CSV file
+    pub fn parse_address_list(data: &str) -> Vec<String> {
+        let prefix = format!("{}1", get_address_prefix());
+        data.split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+            .map(|value| value.trim_matches(|c| c == '"' || c == '\''))
+            .filter(|value| value.starts_with(&prefix))
+            .map(|value| value.to_owned())
+            .collect()
+    }
+
+    /// Replace the group's address list, storing the addresses themselves unless `root_only`.
+    /// The tree's leaves are always stored, so members can be matched and given proofs either way.
+    pub async fn set_address_list(
+        &self,
+        chat_id: String,
+        addresses: Vec<String>,
+        root_only: bool,
+    ) -> Result<GroupRule, AppError> {
+        let addresses = addresses
+            .into_iter()
+            .map(|address| address.trim().to_owned())
+            .filter(|address| !address.is_empty())
+            .collect::<Vec<String>>();
+        let Some(merkle_root) = merkle_root(&addresses) else {
+            return Err(AppError::new(400).message("address list is empty"));
+        };
+
+        let rule = GroupRule {
+            merkle_root: Some(merkle_root),
+            ..GroupRule::new(chat_id, RULE_TYPE_ADDRESS_LIST)
+        };
+        let stored = if root_only { &[][..] } else { &addresses[..] };
+        self.rule_dao
+            .replace_address_list(rule, stored, &leaf_hashes(&addresses))
+            .await
+            .map_err(|e| {
+                AppError::new(500)
+                    .cause(e)
+                    .message("set address list failed")
+            })
+    }
+
+    /// Merkle root and proof of the address on the group's list
+    pub async fn get_address_proof(
+        &self,
+        chat_id: String,
+        address: String,
+    ) -> Result<(String, Vec<String>), AppError> {
+        let rules = self.get_rules(chat_id).await;
+        for rule in rules
+            .iter()
+            .filter(|rule| rule.rule_type == RULE_TYPE_ADDRESS_LIST)
+        {
+            let (Some(root), Ok(leaves)) =
+                (&rule.merkle_root, self.rule_dao.get_leaves(rule.id).await)
+            else {
+                continue;
+            };
+            if let Some(proof) = merkle_proof(&leaves, &address) {
+                return Ok((root.clone(), proof));
+            }
+        }
+
+        Err(AppError::new(404).message("address is not on the group's list"))
+    }
+
+    /// Check the group's minimum balance and every one of its rules.
+    ///
+    /// Groups with an address list only admit the listed addresses, regardless of balance.
+    /// EVM rules are checked against `evm_address`, the member's verified EVM signer if any.
+    pub async fn check_group(
+        &self,
+        group: &TelegramGroup,
+        address: String,
+        evm_address: Option<String>,
+        balances: &Value,
+        merkle_proof: Option<&Vec<String>>,
+    ) -> CheckResult {
+        let rules = self.get_rules(group.chat_id.clone()).await;
+
+        let list_rules = rules
+            .iter()
+            .filter(|rule| rule.rule_type == RULE_TYPE_ADDRESS_LIST)
+            .collect::<Vec<&GroupRule>>();
+        if !list_rules.is_empty() {
+            for rule in list_rules {
+                if self.is_listed(rule, address.clone(), merkle_proof).await {
+                    return Ok(Ok(()));
+                }
+            }
+            return Ok(Err("Address is not on the group's list".to_owned()));
+        }
+
+        let min_balance = group.min_approve_balance.unwrap_or(0) as f64;
+        let token_address = balance_key(&group.token_address);
+
+        let balance = balances
+            .get(&token_address)
+            .and_then(Value::as_f64)
+            .unwrap_or(0.0);
+        if balance < min_balance {
+            return Ok(Err(format!(
+                "Insufficient balance(Min: {} {})",
+                min_balance, token_address
+            )));
+        }
+
+        for rule in rules {
+            if let Err(reason) = self
+                .check_rule(&rule, address.clone(), evm_address.clone(), balances)
+                .await?
+            {
+                return Ok(Err(reason));
+            }
+        }
+
+        Ok(Ok(()))
+    }
+
+    /// Whether the group admits members by address list, which is checked without balances
+    pub async fn has_address_list(&self, chat_id: String) -> bool {
+        self.get_rules(chat_id)
+            .await
+            .iter()
+            .any(|rule| rule.rule_type == RULE_TYPE_ADDRESS_LIST)
+    }
+
+    /// Whether the address or its leaf is stored in the rule's list, or proven against its merkle
+    /// root. Proven addresses are stored so that later checks do not need the proof again.
+    async fn is_listed(
+        &self,
+        rule: &GroupRule,
+        address: String,
+        merkle_proof: Option<&Vec<String>>,
+    ) -> bool {
+        if let Ok(true) = self
+            .rule_dao
+            .is_address_listed(rule.id, address.clone())
+            .await
+        {
+            return true;
+        }
+
+        if let Ok(true) = self
+            .rule_dao
+            .is_leaf_listed(rule.id, leaf_hash(&address))
+            .await
+        {
+            return true;
+        }
+
+        match (&rule.merkle_root, merkle_proof) {
+            (Some(root), Some(proof)) if verify_proof(root, &address, proof) => {
+                let _ = self.rule_dao.add_addresses(rule.id, &[address]).await;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub async fn check_rule(
+        &self,
+        rule: &GroupRule,
+        address: String,
+        evm_address: Option<String>,
+        balances: &Value,
+    ) -> CheckResult {
+        match rule.rule_type {
+            RULE_TYPE_HOLDING_PERIOD => self.check_holding_period(rule, address, balances).await,
+            RULE_TYPE_MIN_TRANSACTIONS => self.check_min_transactions(rule, address).await,
+            RULE_TYPE_MIN_ADDRESS_AGE => self.check_min_address_age(rule, address).await,
+            RULE_TYPE_ERC20_BALANCE | RULE_TYPE_ERC721_OWNERSHIP => {
+                self.check_evm_rule(rule, evm_address).await
+            }
+            RULE_TYPE_DID_ACCOUNT => self.check_did_account(rule, address).await,
+            _ => Ok(Ok(())),
+        }
+    }
+
+    async fn check_holding_period(
+        &self,
+        rule: &GroupRule,
+        address: String,
+        balances: &Value,
+    ) -> CheckResult {
+        let token = rule_token(rule);
+        let min_amount = rule.min_amount.unwrap_or(0) as f64;
+        let min_days = rule.min_days.unwrap_or(0);
+        let reason = format!(
+            "Did not hold {} {} for {} days",
+            min_amount,
+            rule_token(rule),
+            min_days
+        );
+
+        let current = balances.get(&token).and_then(Value::as_f64).unwrap_or(0.0);
+        if current < min_amount {
+            return Ok(Err(reason));
+        }
+
+        let since = Utc::now().naive_utc() - Duration::days(min_days as i64);
+        let held = get_min_balance_since(address, token, current, since).await?;
+        if held < min_amount {
+            return Ok(Err(reason));
+        }
+
+        Ok(Ok(()))
+    }
+
+    async fn check_min_transactions(&self, rule: &GroupRule, address: String) -> CheckResult {
+        let min_transactions = rule.min_amount.unwrap_or(0);
+        let transactions = get_address_attributes(address)
+            .await?
+            .and_then(|attributes| attributes.transactions_count)
+            .and_then(|count| count.parse::<i64>().ok())
+            .unwrap_or(0);
+        if transactions < min_transactions {
+            return Ok(Err(format!(
+                "Address has fewer than {} transactions",
+                min_transactions
+            )));
+        }
+
+        Ok(Ok(()))
+    }
+
+    async fn check_min_address_age(&self, rule: &GroupRule, address: String) -> CheckResult {
+        let min_days = rule.min_days.unwrap_or(0);
+        let since = Utc::now().naive_utc() - Duration::days(min_days as i64);
+        match get_first_transaction_time(address).await? {
+            Some(first_transaction) if first_transaction <= since => Ok(Ok(())),
+            _ => Ok(Err(format!("Address is younger than {} days", min_days))),
+        }
+    }
+
+    async fn check_evm_rule(&self, rule: &GroupRule, evm_address: Option<String>) -> CheckResult {
+        let Some(evm_address) = evm_address else {
+            return Ok(Err("No verified EVM address".to_owned()));
+        };
+
+        let chain_id = rule.chain_id.unwrap_or(0);
+        let contract = rule.token_address.clone().unwrap_or_default();
+        let min_amount = rule.min_amount.unwrap_or(0);
+        let balance = if rule.rule_type == RULE_TYPE_ERC20_BALANCE {
+            get_erc20_balance(chain_id, contract.clone(), evm_address).await?
+        } else {
+            get_erc721_balance(chain_id, contract.clone(), evm_address).await? as f64
+        };
+
+        if balance < min_amount as f64 {
+            return Ok(Err(format!(
+                "Insufficient balance(Min: {} {} on chain {})",
+                min_amount, contract, chain_id
+            )));
+        }
+
+        Ok(Ok(()))
+    }
+
+    async fn check_did_account(&self, rule: &GroupRule, address: String) -> CheckResult {
+        let namespace = did_namespace(rule);
+        let accounts = get_did_accounts(address).await?;
+        let is_owner = accounts.iter().any(|account| match &namespace {
+            Some(namespace) => {
+                account == namespace || account.ends_with(&format!(".{}", namespace))
+            }
+            None => account.ends_with(".bit"),
+        });
+        if !is_owner {
+            return Ok(Err(format!(
+                "No .bit account under {}",
+                namespace.unwrap_or(".bit".to_owned())
+            )));
+        }
+
+        Ok(Ok(()))
+    }
+
+    pub fn describe(rule: &GroupRule) -> String {
+        match rule.rule_type {
+            RULE_TYPE_HOLDING_PERIOD => format!(
+                "Hold at least {} {} for {} days",
+                rule.min_amount.unwrap_or(0),
+                rule_token(rule),
+                rule.min_days.unwrap_or(0)
+            ),
+            RULE_TYPE_ADDRESS_LIST => format!(
+                "Address on the snapshot list (root {})",
+                rule.merkle_root.clone().unwrap_or_default()
+            ),
+            RULE_TYPE_MIN_TRANSACTIONS => format!(
+                "Address has at least {} transactions",
+                rule.min_amount.unwrap_or(0)
+            ),
+            RULE_TYPE_MIN_ADDRESS_AGE => format!(
+                "Address is at least {} days old",
+                rule.min_days.unwrap_or(0)
+            ),
+            RULE_TYPE_ERC20_BALANCE => format!(
+                "Hold at least {} of ERC-20 {} on chain {}",
+                rule.min_amount.unwrap_or(0),
+                rule.token_address.clone().unwrap_or_default(),
+                rule.chain_id.unwrap_or(0)
+            ),
+            RULE_TYPE_ERC721_OWNERSHIP => format!(
+                "Own at least {} of ERC-721 {} on chain {}",
+                rule.min_amount.unwrap_or(1),
+                rule.token_address.clone().unwrap_or_default(),
+                rule.chain_id.unwrap_or(0)
+            ),
+            RULE_TYPE_DID_ACCOUNT => match did_namespace(rule) {
+                Some(namespace) => format!("Own {} or one of its sub-accounts", namespace),
+                None => "Own a .bit account".to_owned(),
+            },
+            _ => "Unknown rule".to_owned(),
+        }
+    }
+}
+
+/// `.bit` account whose sub-accounts the rule requires, `None` for any account
+fn did_namespace(rule: &GroupRule) -> Option<String> {
+    match &rule.token_address {
+        Some(namespace) if !namespace.is_empty() => Some(namespace.clone()),
+        _ => None,
+    }
+}
+
+/// Key of the rule's token in the balances map
+fn rule_token(rule: &GroupRule) -> String {
+    balance_key(&rule.token_address)
+}
+
+/// Key of a gating token in the balances map: its type hash, or "CKB" when unset
+pub(crate) fn balance_key(token_address: &Option<String>) -> String {
+    match token_address {
+        Some(token) if !token.is_empty() && token.to_lowercase() != "ckb" => token.clone(),
+        _ => "CKB".to_owned(),
+    }
+}
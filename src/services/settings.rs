@@ -0,0 +1,271 @@
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup};
+
+use crate::{
+    config::{DEFAULT_BAN_MINUTES, DEFAULT_KYC_MINUTES},
+    models::{
+        telegram::{TelegramGroup, JOIN_MODE_REQUEST, LAPSE_ACTION_REMOVE},
+        token::Token,
+    },
+};
+
+/// Callback data prefix of the /settings panel, followed by `<chat_id>:<action>`
+pub const CALLBACK_SETTINGS: &str = "cfg:";
+
+/// Callback value of the CKB entry in the token picker
+const TOKEN_CKB: &str = "ckb";
+
+/// Hex characters of a type hash kept in the token picker's callback data
+pub const TOKEN_PREFIX_LEN: usize = 16;
+
+/// Numeric group settings, edited with +/- buttons instead of typed numbers
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NumericSetting {
+    Amount,
+    Age,
+    Grace,
+    KycTime,
+    BanTime,
+}
+
+impl NumericSetting {
+    const ALL: [NumericSetting; 5] = [
+        NumericSetting::Amount,
+        NumericSetting::Age,
+        NumericSetting::Grace,
+        NumericSetting::KycTime,
+        NumericSetting::BanTime,
+    ];
+
+    fn key(&self) -> &'static str {
+        match self {
+            NumericSetting::Amount => "amt",
+            NumericSetting::Age => "age",
+            NumericSetting::Grace => "grace",
+            NumericSetting::KycTime => "kyc",
+            NumericSetting::BanTime => "ban",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|setting| setting.key() == key)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            NumericSetting::Amount => "Minimum Balance",
+            NumericSetting::Age => "Minimum Age (years)",
+            NumericSetting::Grace => "Grace Period (hours)",
+            NumericSetting::KycTime => "Verification Time (minutes)",
+            NumericSetting::BanTime => "Ban Cooldown (minutes)",
+        }
+    }
+
+    fn steps(&self) -> [i64; 2] {
+        match self {
+            NumericSetting::Amount => [100, 1000],
+            NumericSetting::Age => [1, 5],
+            NumericSetting::Grace => [1, 12],
+            NumericSetting::KycTime => [1, 10],
+            NumericSetting::BanTime => [10, 60],
+        }
+    }
+
+    fn min(&self) -> i64 {
+        match self {
+            NumericSetting::KycTime | NumericSetting::BanTime => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn get(&self, group: &TelegramGroup) -> i64 {
+        match self {
+            NumericSetting::Amount => group.min_approve_balance.unwrap_or(0),
+            NumericSetting::Age => group.min_approve_age.unwrap_or(0) as i64,
+            NumericSetting::Grace => group.grace_hours.unwrap_or(0) as i64,
+            NumericSetting::KycTime => group.kyc_minutes.unwrap_or(DEFAULT_KYC_MINUTES) as i64,
+            NumericSetting::BanTime => group.ban_minutes.unwrap_or(DEFAULT_BAN_MINUTES) as i64,
+        }
+    }
+
+    /// Store the value, raised to the setting's minimum as callback data can be forged
+    pub fn set(&self, group: &mut TelegramGroup, value: i64) {
+        let value = value.max(self.min());
+        let small = i32::try_from(value).unwrap_or(i32::MAX);
+        match self {
+            NumericSetting::Amount => group.min_approve_balance = Some(value),
+            NumericSetting::Age => group.min_approve_age = Some(small),
+            NumericSetting::Grace => group.grace_hours = Some(small),
+            NumericSetting::KycTime => group.kyc_minutes = Some(small),
+            NumericSetting::BanTime => group.ban_minutes = Some(small),
+        }
+    }
+}
+
+/// What a /settings panel button does
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingsAction {
+    Main,
+    Tokens,
+    /// `ckb`, or the first [`TOKEN_PREFIX_LEN`] hex characters of the type hash
+    PickToken(String),
+    /// Show a draft value of the setting, saving it when confirmed
+    Edit(NumericSetting, i64, bool),
+    ToggleLapseAction,
+    ToggleJoinMode,
+    Close,
+}
+
+impl SettingsAction {
+    fn encode(&self) -> String {
+        match self {
+            SettingsAction::Main => "main".to_owned(),
+            SettingsAction::Tokens => "tok".to_owned(),
+            SettingsAction::PickToken(token) => format!("tk:{}", token),
+            SettingsAction::Edit(setting, value, false) => format!("{}:{}", setting.key(), value),
+            SettingsAction::Edit(setting, value, true) => {
+                format!("{}:{}:ok", setting.key(), value)
+            }
+            SettingsAction::ToggleLapseAction => "lapse".to_owned(),
+            SettingsAction::ToggleJoinMode => "join".to_owned(),
+            SettingsAction::Close => "close".to_owned(),
+        }
+    }
+
+    fn decode(action: &str) -> Option<Self> {
+        let mut parts = action.split(':');
+        let action = match (parts.next()?, parts.next(), parts.next()) {
+            ("main", None, None) => SettingsAction::Main,
+            ("tok", None, None) => SettingsAction::Tokens,
+            ("tk", Some(token), None) => SettingsAction::PickToken(token.to_owned()),
+            ("lapse", None, None) => SettingsAction::ToggleLapseAction,
+            ("join", None, None) => SettingsAction::ToggleJoinMode,
+            ("close", None, None) => SettingsAction::Close,
+            (key, Some(value), confirm) => SettingsAction::Edit(
+                NumericSetting::from_key(key)?,
+                value.parse().ok()?,
+                confirm == Some("ok"),
+            ),
+            _ => return None,
+        };
+        parts.next().is_none().then_some(action)
+    }
+}
+
+/// Callback data of a panel button for the group
+pub fn encode(chat_id: &str, action: &SettingsAction) -> String {
+    format!("{}{}:{}", CALLBACK_SETTINGS, chat_id, action.encode())
+}
+
+/// Group id and action of a panel button's callback data
+pub fn decode(data: &str) -> Option<(String, SettingsAction)> {
+    let (chat_id, action) = data.strip_prefix(CALLBACK_SETTINGS)?.split_once(':')?;
+    Some((chat_id.to_owned(), SettingsAction::decode(action)?))
+}
+
+fn button(chat_id: &str, text: impl Into<String>, action: SettingsAction) -> InlineKeyboardButton {
+    InlineKeyboardButton::callback(text, encode(chat_id, &action))
+}
+
+/// Overview of the group, one button per setting
+pub fn main_keyboard(group: &TelegramGroup) -> InlineKeyboardMarkup {
+    let chat_id = group.chat_id.as_str();
+    let mut rows = vec![vec![button(chat_id, "🪙 Token", SettingsAction::Tokens)]];
+    for pair in NumericSetting::ALL.chunks(2) {
+        rows.push(
+            pair.iter()
+                .map(|setting| {
+                    let text = format!("{}: {}", setting.label(), setting.get(group));
+                    button(
+                        chat_id,
+                        text,
+                        SettingsAction::Edit(*setting, setting.get(group), false),
+                    )
+                })
+                .collect(),
+        );
+    }
+
+    let lapse_action = if group.lapse_action == LAPSE_ACTION_REMOVE {
+        "remove"
+    } else {
+        "restrict"
+    };
+    let join_mode = if group.join_mode == JOIN_MODE_REQUEST {
+        "request"
+    } else {
+        "restrict"
+    };
+    rows.push(vec![
+        button(
+            chat_id,
+            format!("🔁 Lapse: {}", lapse_action),
+            SettingsAction::ToggleLapseAction,
+        ),
+        button(
+            chat_id,
+            format!("🔁 Join: {}", join_mode),
+            SettingsAction::ToggleJoinMode,
+        ),
+    ]);
+    rows.push(vec![button(chat_id, "✖️ Close", SettingsAction::Close)]);
+    InlineKeyboardMarkup::new(rows)
+}
+
+/// Steppers around the draft value, with buttons to save it or go back unchanged
+pub fn edit_keyboard(chat_id: &str, setting: NumericSetting, value: i64) -> InlineKeyboardMarkup {
+    let [small, large] = setting.steps();
+    let step = |delta: i64| {
+        let draft = value.saturating_add(delta).max(setting.min());
+        let text = if delta < 0 {
+            format!("−{}", -delta)
+        } else {
+            format!("+{}", delta)
+        };
+        button(chat_id, text, SettingsAction::Edit(setting, draft, false))
+    };
+    InlineKeyboardMarkup::new(vec![
+        vec![step(-large), step(-small), step(small), step(large)],
+        vec![
+            button(
+                chat_id,
+                "✅ Save",
+                SettingsAction::Edit(setting, value, true),
+            ),
+            button(chat_id, "↩️ Back", SettingsAction::Main),
+        ],
+    ])
+}
+
+/// CKB and the registered tokens to gate the group with
+pub fn token_keyboard(chat_id: &str, tokens: &[Token]) -> InlineKeyboardMarkup {
+    let mut rows = vec![vec![button(
+        chat_id,
+        "CKB",
+        SettingsAction::PickToken(TOKEN_CKB.to_owned()),
+    )]];
+    for token in tokens {
+        let prefix: String = token
+            .type_hash
+            .trim_start_matches("0x")
+            .chars()
+            .take(TOKEN_PREFIX_LEN)
+            .collect();
+        let name = token
+            .symbol
+            .clone()
+            .or(token.name.clone())
+            .unwrap_or_else(|| "Unknown".to_owned());
+        rows.push(vec![button(
+            chat_id,
+            name,
+            SettingsAction::PickToken(prefix),
+        )]);
+    }
+    rows.push(vec![button(chat_id, "↩️ Back", SettingsAction::Main)]);
+    InlineKeyboardMarkup::new(rows)
+}
+
+/// Whether the picked token is CKB rather than a registered token's type hash prefix
+pub fn is_ckb(token: &str) -> bool {
+    token == TOKEN_CKB
+}
@@ -1,11 +1,13 @@
-use std::{str::FromStr, sync::Arc};
+use std::{collections::{hash_map::Entry, HashMap}, str::FromStr, sync::Arc};
 
-use chrono::{NaiveDateTime, Utc};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+use chrono::{Duration, Utc};
 use teloxide::{
-    dispatching::dialogue::GetChatId, payloads::{BanChatMemberSetters, SendMessageSetters}, prelude::*, types::{Chat, ChatKind, ChatMemberStatus, ChatPermissions, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageKind, ParseMode}, utils::command::BotCommands, Bot
+    net::Download, payloads::{BanChatMemberSetters, SendMessageSetters}, prelude::*, types::{CallbackQuery, Chat, ChatJoinRequest, ChatKind, ChatMemberUpdated, User, ChatMemberStatus, ChatPermissions, InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId, ParseMode, WebAppInfo}, utils::{command::BotCommands, markdown}, Bot
 };
 
-use crate::{config::{self, MEMBER_BAN_DURATION, MEMBER_KYC_DURATION}, models::{telegram::{TelegramGroup, TelegramGroupAdmin, TelegramGroupJoined, MEMBER_STATUS_ACCEPTED, MEMBER_STATUS_PENDING, MEMBER_STATUS_REJECT}, token::{Token, TOKEN_TYPE_SPORE, TOKEN_TYPE_XUDT}}, repositories::{ckb::{get_collection_info, get_xudt_info}, member::MemberDao, telegram::TelegramDao, token::TokenDao}};
+use crate::{config::{self, DEFAULT_BAN_MINUTES, DEFAULT_KYC_MINUTES}, models::{exemption::GroupExemption, rule::{GroupRule, RULE_TYPE_DID_ACCOUNT, RULE_TYPE_ERC20_BALANCE, RULE_TYPE_ERC721_OWNERSHIP, RULE_TYPE_HOLDING_PERIOD, RULE_TYPE_MIN_ADDRESS_AGE, RULE_TYPE_MIN_TRANSACTIONS}, tier::GroupTier, token::TokenCandidate, telegram::{format_duration, is_member_present, TelegramGroup, MEMBER_STATUS_BANNED, MEMBER_STATUS_KICKED, MEMBER_STATUS_LEFT, GROUP_STATUS_ACTIVE, GROUP_STATUS_INACTIVE, TelegramGroupAdmin, TelegramGroupJoined, JOIN_MODE_REQUEST, JOIN_MODE_RESTRICT, LAPSE_ACTION_REMOVE, LAPSE_ACTION_RESTRICT, MEMBER_STATUS_ACCEPTED, MEMBER_STATUS_LAPSED, MEMBER_STATUS_PENDING, MEMBER_STATUS_REJECT}}, repositories::{balance::{get_balances, get_member_balances, BalanceResult}, evm::get_evm_rpc, exemption::ExemptionDao, member::MemberDao, telegram::TelegramDao}, services::{rule::RuleSrv, settings::{self, SettingsAction}, tier::TierSrv, token::TokenSrv}};
 
 #[derive(BotCommands, Clone, Debug)]
 #[command(rename_rule = "lowercase", description = "Available commands:")]
@@ -13,8 +15,32 @@ pub enum CommandType {
     SetToken(String),
     SetAmount(i64),
     SetAge(i32),
+    #[command(parse_with = "split")]
+    AddHoldingRule { token: String, amount: i64, days: i32 },
+    AddTxRule(i64),
+    AddAgeRule(i32),
+    #[command(parse_with = "split")]
+    AddErc20Rule { chain_id: i64, contract: String, amount: i64 },
+    #[command(parse_with = "split")]
+    AddErc721Rule { chain_id: i64, contract: String, count: i64 },
+    AddDidRule(String),
+    ListRules,
+    DelRule(i32),
+    #[command(parse_with = "split")]
+    AddTier { name: String, token: String, amount: i64, permissions: String },
+    ListTiers,
+    DelTier(i32),
+    SetGrace(i32),
+    SetLapseAction(String),
+    SetJoinMode(String),
+    SetKycTime(i32),
+    SetBanTime(i32),
+    Exempt(String),
+    Unexempt(String),
     GroupConfig,
+    Settings,
     ListUsers,
+    CheckBalance(String),
     Help,
 }
 
@@ -24,46 +50,130 @@ pub enum PrivateCommandType {
     MyGroups,
     GroupConfig(String),
     ListUsers(String),
+    UploadList(String),
+    Refresh,
+    Start(String),
+    Settings(String),
+}
+
+/// Payload prefix of the `/start` deep links that open the verification of a group
+pub const START_VERIFY: &str = "verify_";
+
+/// Callback data prefix of the /mygroups buttons picking the group to manage in private
+pub const CALLBACK_SELECT_GROUP: &str = "mygroup:";
+
+/// Callback data prefix of the /settoken picker, followed by `<chat_id>:<encoded type hash>`.
+/// Kept short so the data fits Telegram's 64 byte limit.
+pub const CALLBACK_SET_TOKEN: &str = "stk:";
+
+/// Mini App button verifying the user for a group, `webapp_url` falling back to `kyc_link`.
+/// Unset or empty values are skipped, `None` if neither is a valid URL.
+fn verify_keyboard(chat_id: &str, user_id: i64) -> Option<InlineKeyboardMarkup> {
+    let configured = |key: &str| config::CONFIG.get::<String>(key).ok().filter(|value| !value.trim().is_empty());
+    let Some(mut url) = configured("webapp_url")
+        .or_else(|| configured("kyc_link"))
+        .and_then(|webapp_url| reqwest::Url::from_str(webapp_url.trim()).ok())
+    else {
+        log::error!("Neither webapp_url nor kyc_link is a valid URL, cannot offer verification");
+        return None;
+    };
+    url.query_pairs_mut().append_pair("chat_id", chat_id).append_pair("tgid", &user_id.to_string());
+    Some(InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::web_app("Verify", WebAppInfo { url })]]))
+}
+
+/// Whether the text is a `0x` prefixed 32 byte type script hash
+fn is_type_hash(text: &str) -> bool {
+    text.strip_prefix("0x")
+        .is_some_and(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Type hashes are base64 encoded in callback data, which Telegram limits to 64 bytes
+fn encode_type_hash(type_hash: &str) -> String {
+    let bytes = hex::decode(type_hash.trim_start_matches("0x")).unwrap_or_default();
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn decode_type_hash(encoded: &str) -> Option<String> {
+    let bytes = URL_SAFE_NO_PAD.decode(encoded).ok()?;
+    Some(format!("0x{}", hex::encode(bytes)))
+}
+
+fn describe_candidate(candidate: &TokenCandidate) -> String {
+    let hash = &candidate.type_hash;
+    let short_hash = if hash.len() > 14 {
+        format!("{}…{}", &hash[..8], &hash[hash.len() - 4..])
+    } else {
+        hash.clone()
+    };
+    let decimal = candidate.decimal.clone().filter(|d| !d.is_empty()).unwrap_or("?".to_owned());
+    let holders = candidate.holders.map(|h| h.to_string()).unwrap_or("?".to_owned());
+    format!("{} · {} · {} decimals · {} holders", candidate.label(), short_hash, decimal, holders)
 }
 
 #[derive(Clone, Debug)]
 pub struct TelegramService {
     pub member_dao: Arc<MemberDao>,
     pub tele_dao: Arc<TelegramDao>,
-    pub token_dao: Arc<TokenDao>,
+    pub token_srv: Arc<TokenSrv>,
+    pub rule_srv: Arc<RuleSrv>,
+    pub tier_srv: Arc<TierSrv>,
+    pub exemption_dao: Arc<ExemptionDao>,
     pub bot: Bot,
 }
 
 impl TelegramService {
-    pub fn new(member_dao: Arc<MemberDao>, tele_dao: Arc<TelegramDao>, token_dao: Arc<TokenDao>) -> Self {
+    pub fn new(member_dao: Arc<MemberDao>, tele_dao: Arc<TelegramDao>, token_srv: Arc<TokenSrv>, rule_srv: Arc<RuleSrv>, tier_srv: Arc<TierSrv>, exemption_dao: Arc<ExemptionDao>) -> Self {
         let bot_token: String = config::get("bot_token");
         TelegramService {
             member_dao: member_dao.clone(),
             tele_dao: tele_dao.clone(),
-            token_dao: token_dao.clone(),
+            token_srv: token_srv.clone(),
+            rule_srv: rule_srv.clone(),
+            tier_srv: tier_srv.clone(),
+            exemption_dao: exemption_dao.clone(),
             bot: Bot::new(bot_token)
         }
     }
 
     pub async fn start(self: Arc<Self>){
         println!("Telegram Bot Running....");
-        teloxide::repl(self.bot.clone(), {
-            move |bot: Bot, message: Message| {
-                let service: Arc<TelegramService> = Arc::clone(&self);
-                async move {
-                    let chat = message.chat.clone();
-                    if chat.is_group() || chat.is_supergroup() {
-                        service.handle_message(&bot, message).await;
-                    } else if let ChatKind::Private(..) = chat.clone().kind{
-                        if let Ok(command) = PrivateCommandType::parse(message.text().unwrap_or(""), "bot") {
-                            service.handle_private_command(&bot, message, command).await;
-                        }
+        let handler = dptree::entry()
+            .branch(Update::filter_message().endpoint(|bot: Bot, message: Message, service: Arc<TelegramService>| async move {
+                let chat = message.chat.clone();
+                if chat.is_group() || chat.is_supergroup() {
+                    service.handle_message(&bot, message).await;
+                } else if let ChatKind::Private(..) = chat.clone().kind{
+                    if let Ok(command) = PrivateCommandType::parse(message.text().or(message.caption()).unwrap_or(""), "bot") {
+                        service.handle_private_command(&bot, message, command).await;
+                    } else if message.text().is_some_and(|text| text.starts_with('/')) {
+                        service.handle_private_admin_command(&bot, message).await;
                     }
-                    respond(())
                 }
-            }
-        })
-        .await;
+                respond(())
+            }))
+            .branch(Update::filter_chat_member().endpoint(|bot: Bot, update: ChatMemberUpdated, service: Arc<TelegramService>| async move {
+                service.handle_chat_member(&bot, update).await;
+                respond(())
+            }))
+            .branch(Update::filter_my_chat_member().endpoint(|bot: Bot, update: ChatMemberUpdated, service: Arc<TelegramService>| async move {
+                service.handle_my_chat_member(&bot, update).await;
+                respond(())
+            }))
+            .branch(Update::filter_chat_join_request().endpoint(|bot: Bot, request: ChatJoinRequest, service: Arc<TelegramService>| async move {
+                service.handle_join_request(&bot, request).await;
+                respond(())
+            }))
+            .branch(Update::filter_callback_query().endpoint(|bot: Bot, query: CallbackQuery, service: Arc<TelegramService>| async move {
+                service.handle_callback_query(&bot, query).await;
+                respond(())
+            }));
+
+        Dispatcher::builder(self.bot.clone(), handler)
+            .dependencies(dptree::deps![self.clone()])
+            .enable_ctrlc_handler()
+            .build()
+            .dispatch()
+            .await;
     }
 
     pub async fn update_group_admin(&self, bot: Bot, chat: Chat) {
@@ -73,6 +183,7 @@ impl TelegramService {
                     let _ = self.tele_dao.add_admin(TelegramGroupAdmin{ 
                         chat_id: chat.id.to_string(), 
                         user_id: user.user.id.0 as i64, 
+                        selected_at: None,
                         created_at: Utc::now().naive_utc(), 
                         updated_at: Utc::now().naive_utc() }).await;
                 }
@@ -80,104 +191,299 @@ impl TelegramService {
     }
 
     pub async fn handle_message(&self, bot: &Bot, message: Message) {
-        let chat = message.chat.clone();
         let text = message.text().unwrap_or("");
 
-        // Handle new chat members
-        if let MessageKind::NewChatMembers(msg) = &message.kind.clone(){
-            // if telegram user is admin, then add him/her to group_admins
-            self.update_group_admin(bot.clone(), chat.clone()).await;
+        if text.starts_with("/"){
+            if let Ok(command) = CommandType::parse(text, "bot") {
+                self.handle_command(bot, message.clone(), command).await;
+            }
+        }
+    }
 
-            // create a new group if not exist            
-            let group = self.get_group_or_create(chat.clone()).await.unwrap();
-            
-            for user in msg.clone().new_chat_members {
-                if user.is_bot {
-                    continue
-                }
-                
-                let tgid = user.id;
-                let permissions = ChatPermissions::empty();
-                let _ = bot.restrict_chat_member(chat.id, tgid, permissions).await;
-                
-                let tgname: String = user.clone().username.unwrap_or(user.full_name());
-                let kyc_link: String = config::get("kyc_link");
-                let keyboard =
-                        InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::url(
-                            "Visit",
-                            reqwest::Url::from_str(kyc_link.as_str()).unwrap(),
-                        )]]);
-                
-                // send welcome message
-                if let Err(err) = bot
-                    .send_message(
-                        message.chat_id().unwrap(),
-                        format!(
-                            "Hello @{tgname}, welcome to the group! 👋\nPlease complete your information to get started.\n"
-                        ),
-                    ).parse_mode(ParseMode::Html)
-                    .reply_markup(keyboard.clone())
-                    .await
-                {
-                    log::error!(
-                        "Could not message {tgname} (ID: {tgid}). Error: {:?}",
-                        err
-                    );
-                } else {
-                    // add new member
-                    if let Err(err) = self.member_dao.insert_member(tgid.0 as i64, tgname.clone()).await
-                    {
-                        log::error!("insert new member failed: {:?}", err);
-                    }
+    /// Gate a user who just joined: restrict them and post the verification link, unless exempt
+    async fn handle_member_joined(&self, bot: &Bot, chat: Chat, group: &TelegramGroup, user: User) {
+        let tgid = user.id;
+        let tgname: String = user.clone().username.unwrap_or(user.full_name());
+        if self.exemption_dao.is_exempt(chat.id.to_string(), tgid.0 as i64, user.username.clone(), None).await.unwrap_or(false) {
+            let now = Utc::now().naive_utc();
+            self.accept_exempt_member(TelegramGroupJoined {
+                chat_id: chat.id.to_string(),
+                user_id: tgid.0 as i64,
+                user_name: tgname,
+                ckb_address: None,
+                dob: None,
+                status: MEMBER_STATUS_ACCEPTED,
+                balances: Some("{}".to_owned()),
+                expired: now,
+                tier_id: None,
+                checked_at: None,
+                warned_at: None,
+                fail_count: 0,
+                evm_address: None,
+                btc_address: None,
+                balance_retries: 0,
+                retry_at: None,
+                via_request: false,
+                created_at: now,
+                updated_at: now,
+            }).await;
+            return
+        }
 
-                    // add new member to telegram group
-                    let member_joined =self.tele_dao.get_member(chat.id.to_string(), tgid.0 as i64).await.unwrap();
-                    let expired = Utc::now().naive_utc() + MEMBER_KYC_DURATION;
-                    if member_joined.is_none() {
-                        let _ = self.tele_dao.add_member(TelegramGroupJoined{
-                            chat_id: chat.id.to_string(), 
-                            user_id: tgid.0 as i64,
-                            user_name: tgname.clone(), 
-                            ckb_address: None,
-                            dob: None,
-                            status: MEMBER_STATUS_PENDING,
-                            balances: Some("{}".to_owned()),
-                            expired,
-                            created_at: Utc::now().naive_utc(), 
-                            updated_at: Utc::now().naive_utc() 
-                        }).await;
+        let permissions = ChatPermissions::empty();
+        let _ = bot.restrict_chat_member(chat.id, tgid, permissions).await;
+        
+        // Mini App buttons only work in private chats, so the group gets a deep link to the bot
+        // An unset, empty or invalid kyc_link leaves the prompt without a button
+        let keyboard = match self.verify_deep_link(&chat.id.to_string()).await {
+            Some(deep_link) => Some(InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::url("Verify", deep_link)]])),
+            None => config::CONFIG
+                .get::<String>("kyc_link")
+                .ok()
+                .and_then(|kyc_link| reqwest::Url::from_str(kyc_link.trim()).ok())
+                .map(|kyc_link| InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::url("Visit", kyc_link)]])),
+        };
+        if keyboard.is_none() {
+            log::error!("kyc_link is not a valid URL, cannot offer verification");
+        }
+        
+        // send welcome message
+        let mut welcome = bot
+            .send_message(
+                chat.id,
+                format!(
+                    "Hello @{tgname}, welcome to the group! 👋\nPlease complete your information to get started.\n"
+                ),
+            ).parse_mode(ParseMode::Html);
+        if let Some(keyboard) = keyboard {
+            welcome = welcome.reply_markup(keyboard);
+        }
+        if let Err(err) = welcome.await
+        {
+            log::error!(
+                "Could not message {tgname} (ID: {tgid}). Error: {:?}",
+                err
+            );
+        } else {
+            // add new member
+            if let Err(err) = self.member_dao.insert_member(tgid.0 as i64, tgname.clone()).await
+            {
+                log::error!("insert new member failed: {:?}", err);
+            }
+
+            // add new member to telegram group
+            let member_joined =self.tele_dao.get_member(chat.id.to_string(), tgid.0 as i64).await.unwrap();
+            let expired = Utc::now().naive_utc() + group.kyc_duration();
+            if member_joined.is_none() {
+                let _ = self.tele_dao.add_member(TelegramGroupJoined{
+                    chat_id: chat.id.to_string(), 
+                    user_id: tgid.0 as i64,
+                    user_name: tgname.clone(), 
+                    ckb_address: None,
+                    dob: None,
+                    status: MEMBER_STATUS_PENDING,
+                    balances: Some("{}".to_owned()),
+                    expired,
+                    tier_id: None,
+                    checked_at: None,
+                    warned_at: None,
+                    fail_count: 0,
+                    evm_address: None,
+                    btc_address: None,
+                    balance_retries: 0,
+                    retry_at: None,
+                    via_request: false,
+                    created_at: Utc::now().naive_utc(), 
+                    updated_at: Utc::now().naive_utc() 
+                }).await;
+            } else {
+                let _ = self.tele_dao.update_member(None, None, chat.id.to_string(), tgid.0 as i64, expired, MEMBER_STATUS_PENDING, "{}".to_owned()).await;
+                let _ = self.tele_dao.update_member_via_request(chat.id.to_string(), tgid.0 as i64, false).await;
+            }
+        }
+
+        // send current group settings
+        if let Err(err) = bot
+            .send_message(
+                chat.id,
+                self.render_group_config(group.clone()).await,
+                )
+            .parse_mode(ParseMode::MarkdownV2)
+            .await
+        {
+            log::error!(
+                "Could not message {tgname} (ID: {tgid}). Error: {:?}",
+                err
+            );
+        }
+    }
+
+    /// Queue a join request for verification and send the applicant the verification link in private
+    pub async fn handle_join_request(&self, bot: &Bot, request: ChatJoinRequest) {
+        let chat = request.chat.clone();
+        let Some(group) = self.get_group_or_create(chat.clone()).await else {
+            return
+        };
+        if group.status != GROUP_STATUS_ACTIVE || group.join_mode != JOIN_MODE_REQUEST {
+            return
+        }
+
+        let user = request.from.clone();
+        let tgid = user.id;
+        let tgname: String = user.clone().username.unwrap_or(user.full_name());
+        let now = Utc::now().naive_utc();
+        let member = TelegramGroupJoined {
+            chat_id: chat.id.to_string(),
+            user_id: tgid.0 as i64,
+            user_name: tgname.clone(),
+            ckb_address: None,
+            dob: None,
+            status: MEMBER_STATUS_PENDING,
+            balances: Some("{}".to_owned()),
+            expired: now + group.kyc_duration(),
+            tier_id: None,
+            checked_at: None,
+            warned_at: None,
+            fail_count: 0,
+            evm_address: None,
+            btc_address: None,
+            balance_retries: 0,
+            retry_at: None,
+            via_request: true,
+            created_at: now,
+            updated_at: now,
+        };
+
+        if self.exemption_dao.is_exempt(chat.id.to_string(), tgid.0 as i64, user.username.clone(), None).await.unwrap_or(false) {
+            self.accept_exempt_member(TelegramGroupJoined { status: MEMBER_STATUS_ACCEPTED, ..member }).await;
+            return
+        }
+
+        if let Err(err) = self.member_dao.insert_member(tgid.0 as i64, tgname.clone()).await {
+            log::error!("insert new member failed: {:?}", err);
+        }
+        match self.tele_dao.get_member(chat.id.to_string(), tgid.0 as i64).await {
+            Ok(None) => {
+                let _ = self.tele_dao.add_member(member.clone()).await;
+            }
+            Ok(Some(_)) => {
+                let _ = self.tele_dao.update_member(None, None, chat.id.to_string(), tgid.0 as i64, member.expired, MEMBER_STATUS_PENDING, "{}".to_owned()).await;
+                let _ = self.tele_dao.update_member_via_request(chat.id.to_string(), tgid.0 as i64, true).await;
+            }
+            Err(err) => {
+                log::error!("load join request member failed: {:?}", err);
+                return
+            }
+        }
+
+        let mut greeting = bot.send_message(
+            request.user_chat_id,
+            format!(
+                "Hello @{tgname}, thanks for your request to join {}! 👋\nPlease complete your information within {} to get approved.\n",
+                group.name,
+                format_duration(group.kyc_duration())
+            ),
+        );
+        if let Some(keyboard) = verify_keyboard(&chat.id.to_string(), tgid.0 as i64) {
+            greeting = greeting.reply_markup(keyboard);
+        }
+        if let Err(err) = greeting.await {
+            log::error!(
+                "Could not message {tgname} (ID: {tgid}). Error: {:?}",
+                err
+            );
+            return
+        }
+
+        let _ = bot
+            .send_message(request.user_chat_id, self.render_group_config(group.clone()).await)
+            .parse_mode(ParseMode::MarkdownV2)
+            .await;
+    }
+
+    /// Apply tier permissions to an applicant approved by verification, gating anyone approved by hand
+    async fn handle_request_member_joined(&self, bot: &Bot, chat: Chat, group: &TelegramGroup, user: User) {
+        let member = self.tele_dao.get_member(chat.id.to_string(), user.id.0 as i64).await.unwrap_or(None);
+        match member {
+            Some(member) if self.is_member_exempt(&member).await => self.accept_exempt_member(member).await,
+            Some(member) if member.status == MEMBER_STATUS_ACCEPTED => {
+                let balances = serde_json::from_str(&member.balances.clone().unwrap_or_default()).unwrap_or(serde_json::json!({}));
+                let (tier, permissions) = self.tier_srv.resolve_permissions(member.chat_id.clone(), &balances).await;
+                let _ = bot.restrict_chat_member(chat.id, user.id, permissions).await;
+                let _ = self.tele_dao.update_member_tier(member.chat_id, member.user_id, tier.map(|tier| tier.id)).await;
+            }
+            _ => self.handle_member_joined(bot, chat, group, user).await,
+        }
+    }
+
+    /// Track joins, leaves, kicks and admin changes of group members
+    pub async fn handle_chat_member(&self, bot: &Bot, update: ChatMemberUpdated) {
+        let chat = update.chat.clone();
+        let user = update.new_chat_member.user.clone();
+        if !(chat.is_group() || chat.is_supergroup()) || user.is_bot {
+            return
+        }
+
+        let (old, new) = (&update.old_chat_member.kind, &update.new_chat_member.kind);
+        if !old.is_present() && new.is_present() {
+            if let Some(group) = self.get_group_or_create(chat.clone()).await {
+                if group.status == GROUP_STATUS_ACTIVE {
+                    if update.via_join_request && group.join_mode == JOIN_MODE_REQUEST {
+                        self.handle_request_member_joined(bot, chat.clone(), &group, user.clone()).await;
                     } else {
-                        let _ = self.tele_dao.update_member(None, None, chat.id.to_string(), tgid.0 as i64, expired, MEMBER_STATUS_PENDING, "{}".to_owned()).await;
+                        self.handle_member_joined(bot, chat.clone(), &group, user.clone()).await;
                     }
                 }
+            }
+        } else if old.is_present() && !new.is_present() {
+            let status = if new.is_banned() {
+                MEMBER_STATUS_BANNED
+            } else if update.from.id != user.id {
+                MEMBER_STATUS_KICKED
+            } else {
+                MEMBER_STATUS_LEFT
+            };
+            let _ = self.tele_dao.update_member_status(chat.id.to_string(), user.id.0 as i64, status).await;
+        }
 
-                // send current group settings
-                if let Err(err) = bot
-                    .send_message(
-                        message.chat_id().unwrap(),
-                        self.render_group_config(group.clone()).await,
-                        )
-                    .parse_mode(ParseMode::MarkdownV2)
-                    .await
-                {
-                    log::error!(
-                        "Could not message {tgname} (ID: {tgid}). Error: {:?}",
-                        err
-                    );
-                }
-                
+        if !old.is_privileged() && new.is_privileged() {
+            let _ = self.tele_dao.add_admin(TelegramGroupAdmin {
+                chat_id: chat.id.to_string(),
+                user_id: user.id.0 as i64,
+                selected_at: None,
+                created_at: Utc::now().naive_utc(),
+                updated_at: Utc::now().naive_utc(),
+            }).await;
+        } else if old.is_privileged() && !new.is_privileged() {
+            let _ = self.tele_dao.delete_admin(chat.id.to_string(), user.id.0 as i64).await;
+        }
+    }
+
+    /// Activate groups the bot is added to and deactivate the ones it is removed from
+    pub async fn handle_my_chat_member(&self, bot: &Bot, update: ChatMemberUpdated) {
+        let chat = update.chat.clone();
+        if !(chat.is_group() || chat.is_supergroup()) {
+            return
+        }
+
+        let (old, new) = (&update.old_chat_member.kind, &update.new_chat_member.kind);
+        if new.is_present() {
+            if self.get_group_or_create(chat.clone()).await.is_some() {
+                let _ = self.tele_dao.update_group_status(chat.id.to_string(), GROUP_STATUS_ACTIVE).await;
             }
-        } else if text.starts_with("/"){
-            if let Ok(command) = CommandType::parse(text, "bot") {
-                self.handle_command(bot, message.clone(), command).await;
+            if !old.is_privileged() && new.is_privileged() {
+                self.update_group_admin(bot.clone(), chat).await;
             }
+        } else if old.is_present() {
+            log::info!("Bot was removed from chat {}", chat.id);
+            let _ = self.tele_dao.update_group_status(chat.id.to_string(), GROUP_STATUS_INACTIVE).await;
         }
     }
 
     async fn render_group_config(&self, group: TelegramGroup) -> String {
         let mut token_info: String = "".to_owned();
-        if let Some(type_hash) = group.token_address {
-            if let Some(token) = self.fetch_token(type_hash).await {
+        if let Some(type_hash) = group.token_address.clone() {
+            if let Some(token) = self.token_srv.fetch_token(type_hash).await {
                 token_info = format!(
                     "📦 Token Gating: {}\n🔹 Type Hash: {}\n", 
                     token.name.unwrap(),
@@ -192,6 +498,34 @@ impl TelegramService {
         table.push_str("\n\n⚙️ Current Settings \\(Admin Only\\)\n\n");
         table.push_str(&token_info.to_string());
         table.push_str(&format!("👤 Minimum Age: {}\n💰 Minimum Balance: {}\n", group.min_approve_age.unwrap_or(0), group.min_approve_balance.unwrap_or(0)));
+        let lapse_action = if group.lapse_action == LAPSE_ACTION_REMOVE { "remove" } else { "restrict" };
+        table.push_str(&format!("⏳ Grace Period: {} hours, then {}\n", group.grace_hours.unwrap_or(0), lapse_action));
+        let join_mode = if group.join_mode == JOIN_MODE_REQUEST { "approve join requests" } else { "restrict until verified" };
+        table.push_str(&format!("🚪 Join Mode: {}\n", join_mode));
+        table.push_str(&format!("⏱️ Verification Time: {}\n🚷 Ban Cooldown: {}\n", format_duration(group.kyc_duration()), format_duration(group.ban_duration(1))));
+        table.push_str(&self.render_group_rules(group.chat_id.clone()).await);
+        table.push_str(&self.render_group_tiers(group.chat_id.clone()).await);
+        let exemptions = self.exemption_dao.get_exemptions_by_group(group.chat_id).await.unwrap_or_default();
+        if !exemptions.is_empty() {
+            let names = exemptions.iter().map(|exemption| exemption.describe()).collect::<Vec<String>>();
+            table.push_str(&format!("🛡️ Exempt: {}\n", markdown::escape(&names.join(", "))));
+        }
+        table
+    }
+
+    async fn render_group_rules(&self, chat_id: String) -> String {
+        let mut table = String::new();
+        for rule in self.rule_srv.get_rules(chat_id).await {
+            table.push_str(&format!("📜 Rule {}: {}\n", rule.id, markdown::escape(&RuleSrv::describe(&rule))));
+        }
+        table
+    }
+
+    async fn render_group_tiers(&self, chat_id: String) -> String {
+        let mut table = String::new();
+        for tier in self.tier_srv.get_tiers(chat_id).await {
+            table.push_str(&format!("🏅 Tier {}: {}\n", tier.id, markdown::escape(&TierSrv::describe(&tier))));
+        }
         table
     }
 
@@ -212,95 +546,566 @@ impl TelegramService {
             return
         }
 
-        if let Some(mut group) = self.get_group_or_create(chat.clone()).await {
-            match command {
-                CommandType::SetToken(type_hash) => {
-                    group.token_address = Some(type_hash.clone().to_lowercase()); 
-                    if let Some(token) = self.fetch_token(type_hash).await {
-                        group.token_address = Some(token.type_hash.clone());
-                        let is_updated = self.tele_dao.update_group(&group).await.unwrap_or(false);
-
-                        let token_name = token
-                                                    .name                       
-                                                    .clone()                    
-                                                    .unwrap_or_else(|| "Unknown".to_string());
-                        let reply = if is_updated {
-                            format!("🟢 **Update token: {} successfully!**", token_name)
-                        } else {
-                            "🔴 **Update token failed!**\nPlease try again later or contact admin for support."
-                                .to_string()
-                        };
-
-                        bot.send_message(
-                            chat.id,
-                            reply,
-                        )
+        if let Some(group) = self.get_group_or_create(chat.clone()).await {
+            self.run_admin_command(bot, chat, group, command).await;
+        }
+    }
+
+    /// Run an admin command on the group, replying in the chat it came from, the group or private
+    async fn run_admin_command(&self, bot: &Bot, chat: Chat, mut group: TelegramGroup, command: CommandType) {
+        match command {
+            CommandType::SetToken(query) => {
+                let query = query.trim().to_owned();
+                if query.is_empty() {
+                    bot.send_message(chat.id, "🔴 Usage: /settoken (type_script_hash|symbol|name|ckb)").await.unwrap();
+                } else if query.eq_ignore_ascii_case("ckb") || is_type_hash(&query) {
+                    let reply = self.set_group_token(group, query).await;
+                    bot.send_message(chat.id, reply).await.unwrap();
+                } else {
+                    let candidates = self.token_srv.find_candidates(query.clone()).await;
+                    match candidates.as_slice() {
+                        [] => {
+                            bot.send_message(chat.id, format!("🔴 No token matches \"{}\".", query)).await.unwrap();
+                        }
+                        [candidate] => {
+                            let reply = self.set_group_token(group, candidate.type_hash.clone()).await;
+                            bot.send_message(chat.id, reply).await.unwrap();
+                        }
+                        _ => {
+                            let buttons = candidates.iter().map(|candidate| {
+                                vec![InlineKeyboardButton::callback(
+                                    describe_candidate(candidate),
+                                    format!("{}{}:{}", CALLBACK_SET_TOKEN, group.chat_id, encode_type_hash(&candidate.type_hash)),
+                                )]
+                            });
+                            bot.send_message(chat.id, format!("🔎 Several tokens match \"{}\", pick one:", query))
+                                .reply_markup(InlineKeyboardMarkup::new(buttons))
+                                .await
+                                .unwrap();
+                        }
+                    }
+                }
+            }
+            CommandType::SetAmount(amount) => {
+                group.min_approve_balance = Some(amount);
+                self.save_group_settings(bot, chat, &group).await;
+            }
+            CommandType::SetAge(age) => {
+                group.min_approve_age = Some(age);
+                self.save_group_settings(bot, chat, &group).await;
+            }
+            CommandType::AddHoldingRule { token, amount, days } => {
+                let token_address = match self.token_srv.fetch_token(token).await {
+                    Some(token) if !token.type_hash.is_empty() => Some(token.type_hash),
+                    Some(_) => None,
+                    None => {
+                        bot.send_message(chat.id, "🔴 **Add rule failed!**\n Invalid Type Hash")
+                            .await
+                            .unwrap();
+                        return
+                    }
+                };
+
+                self.add_group_rule(bot.clone(), chat.clone(), GroupRule {
+                    token_address,
+                    min_amount: Some(amount),
+                    min_days: Some(days),
+                    ..GroupRule::new(group.chat_id, RULE_TYPE_HOLDING_PERIOD)
+                }).await;
+            },
+            CommandType::AddTxRule(count) => {
+                self.add_group_rule(bot.clone(), chat.clone(), GroupRule {
+                    min_amount: Some(count),
+                    ..GroupRule::new(group.chat_id, RULE_TYPE_MIN_TRANSACTIONS)
+                }).await;
+            },
+            CommandType::AddAgeRule(days) => {
+                self.add_group_rule(bot.clone(), chat.clone(), GroupRule {
+                    min_days: Some(days),
+                    ..GroupRule::new(group.chat_id, RULE_TYPE_MIN_ADDRESS_AGE)
+                }).await;
+            },
+            CommandType::AddErc20Rule { chain_id, contract, amount } => {
+                self.add_evm_rule(bot.clone(), chat.clone(), group.chat_id, RULE_TYPE_ERC20_BALANCE, chain_id, contract, amount).await;
+            },
+            CommandType::AddErc721Rule { chain_id, contract, count } => {
+                self.add_evm_rule(bot.clone(), chat.clone(), group.chat_id, RULE_TYPE_ERC721_OWNERSHIP, chain_id, contract, count).await;
+            },
+            CommandType::AddDidRule(namespace) => {
+                let namespace = namespace.trim().trim_start_matches('.').to_lowercase();
+                if !namespace.is_empty() && !namespace.ends_with(".bit") {
+                    bot.send_message(chat.id, "🔴 Add rule failed: namespace must be a .bit account")
                         .await
                         .unwrap();
-                    } else {
-                        bot.send_message(
-                            chat.id,
-                            "🔴 **Update token failed!**\n Invalid Type Hash",
-                        )
+                    return
+                }
+
+                self.add_group_rule(bot.clone(), chat.clone(), GroupRule {
+                    token_address: Some(namespace).filter(|namespace| !namespace.is_empty()),
+                    ..GroupRule::new(group.chat_id, RULE_TYPE_DID_ACCOUNT)
+                }).await;
+            },
+            CommandType::ListRules => {
+                let mut table = self.render_group_rules(group.chat_id).await;
+                if table.is_empty() {
+                    table = String::from("No rules configured for this group\\.");
+                }
+                bot.send_message(chat.id, table)
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await
+                    .unwrap();
+            },
+            CommandType::DelRule(id) => {
+                let reply = match self.rule_srv.delete_rule(group.chat_id, id).await {
+                    Ok(true) => format!("🟢 Rule {} removed.", id),
+                    Ok(false) => format!("🔴 Rule {} not found.", id),
+                    Err(err) => format!("🔴 Remove rule failed: {}", err),
+                };
+                bot.send_message(chat.id, reply).await.unwrap();
+            },
+            CommandType::AddTier { name, token, amount, permissions } => {
+                let Some(permissions) = GroupTier::parse_permissions(&permissions) else {
+                    bot.send_message(chat.id, "🔴 **Add tier failed!**\n Permissions must be a comma separated list of text, media, previews, polls")
                         .await
                         .unwrap();
+                    return
+                };
+                let token_address = match self.token_srv.fetch_token(token).await {
+                    Some(token) if !token.type_hash.is_empty() => Some(token.type_hash),
+                    Some(_) => None,
+                    None => {
+                        bot.send_message(chat.id, "🔴 **Add tier failed!**\n Invalid Type Hash")
+                            .await
+                            .unwrap();
+                        return
                     }
+                };
+
+                let now = Utc::now().naive_utc();
+                let reply = match self.tier_srv.add_tier(GroupTier {
+                    id: 0,
+                    chat_id: group.chat_id.clone(),
+                    name,
+                    token_address,
+                    min_balance: Some(amount),
+                    permissions,
+                    created_at: now,
+                    updated_at: now,
+                }).await {
+                    Ok(tier) => format!("🟢 Tier {} added: {}", tier.id, TierSrv::describe(&tier)),
+                    Err(err) => format!("🔴 Add tier failed: {}", err),
+                };
+                bot.send_message(chat.id, reply).await.unwrap();
+            },
+            CommandType::ListTiers => {
+                let mut table = self.render_group_tiers(group.chat_id).await;
+                if table.is_empty() {
+                    table = String::from("No tiers configured for this group\\.");
                 }
-                CommandType::SetAmount(amount) => {
-                    group.min_approve_balance = Some(amount);
-                    match self.tele_dao.update_group(&group).await {
-                        Ok(_) => {
-                            bot.send_message(chat.id, "✅ Group settings updated successfully\\.")
-                                .parse_mode(ParseMode::MarkdownV2)
-                                .await
-                                .unwrap();
-                        },
-                        Err(err) => {
-                            let err_text = format!("⚠️ Failed to update group settings:\n`{}`", &err.to_string());
-                            bot.send_message(chat.id, err_text)
-                                .parse_mode(ParseMode::MarkdownV2)
-                                .await
-                                .unwrap();
-                        },
+                bot.send_message(chat.id, table)
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await
+                    .unwrap();
+            },
+            CommandType::DelTier(id) => {
+                let reply = match self.tier_srv.delete_tier(group.chat_id, id).await {
+                    Ok(true) => format!("🟢 Tier {} removed.", id),
+                    Ok(false) => format!("🔴 Tier {} not found.", id),
+                    Err(err) => format!("🔴 Remove tier failed: {}", err),
+                };
+                bot.send_message(chat.id, reply).await.unwrap();
+            },
+            CommandType::SetGrace(hours) => {
+                if hours < 0 {
+                    bot.send_message(chat.id, "🔴 **Update failed!**\n Grace period cannot be negative")
+                        .await
+                        .unwrap();
+                    return
+                }
+                group.grace_hours = Some(hours);
+                self.save_group_settings(bot, chat, &group).await;
+            },
+            CommandType::SetLapseAction(action) => {
+                group.lapse_action = match action.to_lowercase().as_str() {
+                    "restrict" => LAPSE_ACTION_RESTRICT,
+                    "remove" => LAPSE_ACTION_REMOVE,
+                    _ => {
+                        bot.send_message(chat.id, "🔴 **Update failed!**\n Action must be restrict or remove")
+                            .await
+                            .unwrap();
+                        return
                     }
+                };
+                self.save_group_settings(bot, chat, &group).await;
+            },
+            CommandType::SetJoinMode(mode) => {
+                group.join_mode = match mode.to_lowercase().as_str() {
+                    "restrict" => JOIN_MODE_RESTRICT,
+                    "request" => JOIN_MODE_REQUEST,
+                    _ => {
+                        bot.send_message(chat.id, "🔴 **Update failed!**\n Mode must be restrict or request")
+                            .await
+                            .unwrap();
+                        return
+                    }
+                };
+                self.save_group_settings(bot, chat.clone(), &group).await;
+                if group.join_mode == JOIN_MODE_REQUEST {
+                    bot.send_message(chat.id, "ℹ️ Turn on \"Approve new members\" for the group's invite links so joins go through requests.")
+                        .await
+                        .unwrap();
                 }
-                CommandType::SetAge(age) => {
-                    group.min_approve_age = Some(age);
-                    match self.tele_dao.update_group(&group).await {
-                        Ok(_) => {
-                            bot.send_message(chat.id, "✅ Group settings updated successfully\\.")
-                                .parse_mode(ParseMode::MarkdownV2)
-                                .await
-                                .unwrap();
-                        }
-                        Err(err) => {
-                            let err_text = format!("⚠️ Failed to update group settings:\n`{}`", &err.to_string());
-                            bot.send_message(chat.id, err_text)
-                                .parse_mode(ParseMode::MarkdownV2)
-                                .await
-                                .unwrap();
-                        }
-                    } 
+            },
+            CommandType::SetKycTime(minutes) => {
+                if minutes < 1 {
+                    bot.send_message(chat.id, "🔴 Usage: /setkyctime (minutes), at least 1 minute").await.unwrap();
+                    return
+                }
+                group.kyc_minutes = Some(minutes);
+                self.save_group_settings(bot, chat, &group).await;
+            },
+            CommandType::SetBanTime(minutes) => {
+                // Telegram treats bans shorter than 30 seconds as permanent
+                if minutes < 1 {
+                    bot.send_message(chat.id, "🔴 Usage: /setbantime (minutes), at least 1 minute").await.unwrap();
+                    return
                 }
-                CommandType::GroupConfig => {
-                    self.send_group_config_to_admin(bot.clone(), group.chat_id, chat).await;
-                },
-                CommandType::ListUsers => {
-                    self.send_list_users_to_admin(bot.clone(), group.chat_id, chat).await;
-                },
-                CommandType::Help => {
-                    self.send_help_to_admin(bot.clone(), chat).await;
-                },
+                group.ban_minutes = Some(minutes);
+                self.save_group_settings(bot, chat, &group).await;
+            },
+            CommandType::Exempt(value) => {
+                let exemption = GroupExemption::parse(group.chat_id, &value, Utc::now().naive_utc());
+                let reply = match self.exemption_dao.add_exemption(exemption).await {
+                    Ok(exemption) => format!("🟢 {} is now exempt from gating.", exemption.describe()),
+                    Err(err) => format!("🔴 Add exemption failed: {}", err),
+                };
+                bot.send_message(chat.id, reply).await.unwrap();
+            },
+            CommandType::Unexempt(value) => {
+                let exemption = GroupExemption::parse(group.chat_id, &value, Utc::now().naive_utc());
+                let reply = match self.exemption_dao.delete_exemption(&exemption).await {
+                    Ok(true) => format!("🟢 {} is no longer exempt.", exemption.describe()),
+                    Ok(false) => format!("🔴 {} is not exempt.", exemption.describe()),
+                    Err(err) => format!("🔴 Remove exemption failed: {}", err),
+                };
+                bot.send_message(chat.id, reply).await.unwrap();
+            },
+            CommandType::GroupConfig => {
+                self.send_group_config_to_admin(bot.clone(), group.chat_id, chat).await;
+            },
+            CommandType::Settings => {
+                self.send_settings_panel(bot, chat.id, group).await;
+            },
+            CommandType::ListUsers => {
+                self.send_list_users_to_admin(bot.clone(), group.chat_id, chat).await;
+            },
+            CommandType::CheckBalance(address) => {
+                let reply = match get_balances(address.trim().to_owned()).await {
+                    Ok(balances) => self.render_balances(&balances).await,
+                    Err(err) => format!("🔴 Balance lookup failed: {}", err),
+                };
+                bot.send_message(chat.id, reply).await.unwrap();
+            },
+            CommandType::Help => {
+                self.send_help_to_admin(bot.clone(), chat).await;
+            },
+        }
+    }
+
+    /// Gate the group on the token, fetching its metadata if it is not known yet
+    async fn set_group_token(&self, mut group: TelegramGroup, type_hash: String) -> String {
+        let Some(token) = self.token_srv.fetch_token(type_hash).await else {
+            return "🔴 **Update token failed!**\n Invalid Type Hash".to_owned();
+        };
+
+        group.token_address = Some(token.type_hash.clone());
+        let is_updated = self.tele_dao.update_group(&group).await.unwrap_or(false);
+        let token_name = token.name.clone().unwrap_or_else(|| "Unknown".to_string());
+        if is_updated {
+            format!("🟢 **Update token: {} successfully!**", token_name)
+        } else {
+            "🔴 **Update token failed!**\nPlease try again later or contact admin for support.".to_string()
+        }
+    }
+
+    pub async fn handle_callback_query(&self, bot: &Bot, query: CallbackQuery) {
+        let (Some(data), Some(message)) = (query.data.clone(), query.message.clone()) else {
+            return;
+        };
+        let chat = message.chat().clone();
+
+        if let Some((group_id, encoded)) = data.strip_prefix(CALLBACK_SET_TOKEN).and_then(|data| data.split_once(':')) {
+            // The picker names its group, as the admin may have picked another one since
+            let group = if chat.is_private() {
+                if self.is_group_admin(bot, group_id, query.from.id).await {
+                    self.tele_dao.get_group(group_id.to_owned()).await.unwrap_or(None)
+                } else {
+                    None
+                }
+            } else if chat.id.to_string() == group_id && self.is_chat_admin(bot, chat.id, query.from.id).await {
+                self.get_group_or_create(chat.clone()).await
+            } else {
+                None
+            };
+            if group.is_none() {
+                let _ = bot
+                    .answer_callback_query(query.id.clone())
+                    .text("❌ Only group admins can change the token.")
+                    .await;
+                return;
+            }
+
+            let reply = match (decode_type_hash(encoded), group) {
+                (Some(type_hash), Some(group)) => self.set_group_token(group, type_hash).await,
+                _ => "🔴 **Update token failed!**\n Invalid Type Hash".to_owned(),
+            };
+            let _ = bot.edit_message_text(chat.id, message.id(), reply).await;
+        } else if let Some(group_id) = data.strip_prefix(CALLBACK_SELECT_GROUP) {
+            let reply = match self.resolve_admin_group(bot, query.from.id, group_id).await {
+                Ok(group) => format!("🟢 Now managing {}. Admin commands sent here apply to it, e.g. /setamount 100.", group.name),
+                Err(reply) => reply,
+            };
+            let _ = bot.send_message(chat.id, reply).await;
+        } else if let Some((group_id, action)) = settings::decode(&data) {
+            if !self.is_group_admin(bot, &group_id, query.from.id).await {
+                let _ = bot
+                    .answer_callback_query(query.id.clone())
+                    .text("❌ Only group admins can change the settings.")
+                    .await;
+                return;
             }
+
+            let notice = self.handle_settings_action(bot, chat.id, message.id(), group_id, action).await;
+            let mut answer = bot.answer_callback_query(query.id);
+            if let Some(notice) = notice {
+                answer = answer.text(notice);
+            }
+            let _ = answer.await;
+            return;
         }
+        let _ = bot.answer_callback_query(query.id).await;
+    }
+
+    /// Send the /settings panel of the group, which works the same in the group and in private
+    async fn send_settings_panel(&self, bot: &Bot, chat_id: ChatId, group: TelegramGroup) {
+        bot.send_message(chat_id, self.render_settings(&group).await)
+            .parse_mode(ParseMode::MarkdownV2)
+            .reply_markup(settings::main_keyboard(&group))
+            .await
+            .unwrap();
+    }
+
+    async fn render_settings(&self, group: &TelegramGroup) -> String {
+        format!("🛠️ *{}*{}", markdown::escape(&group.name), self.render_group_config(group.clone()).await)
+    }
+
+    /// Apply a panel button and edit the panel in place, returning the notice to pop up
+    async fn handle_settings_action(&self, bot: &Bot, chat_id: ChatId, message_id: MessageId, group_id: String, action: SettingsAction) -> Option<String> {
+        let Some(mut group) = self.tele_dao.get_group(group_id.clone()).await.unwrap_or(None) else {
+            return Some("🔴 Group not found.".to_owned());
+        };
+
+        let mut notice = None;
+        match action {
+            SettingsAction::Main => {}
+            SettingsAction::Tokens => {
+                let tokens = self.token_srv.search_tokens(None, 8, 0).await.unwrap_or_default();
+                let _ = bot
+                    .edit_message_text(chat_id, message_id, "🪙 Pick the token to gate the group with.\nFor other tokens, use /settoken (symbol|name|type_script_hash).")
+                    .reply_markup(settings::token_keyboard(&group_id, &tokens))
+                    .await;
+                return None;
+            }
+            SettingsAction::PickToken(token) => {
+                let type_hash = if settings::is_ckb(&token) {
+                    Some("ckb".to_owned())
+                } else {
+                    self.token_srv
+                        .search_tokens(Some(token.clone()), 8, 0)
+                        .await
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|token| token.type_hash)
+                        .find(|type_hash| type_hash.trim_start_matches("0x").starts_with(&token))
+                };
+                let Some(type_hash) = type_hash else {
+                    return Some("🔴 Token not found.".to_owned());
+                };
+                notice = Some(self.set_group_token(group.clone(), type_hash).await.replace("**", ""));
+                group = self.tele_dao.get_group(group_id.clone()).await.unwrap_or(None).unwrap_or(group);
+            }
+            SettingsAction::Edit(setting, value, false) => {
+                let text = format!(
+                    "✏️ *{}*\n\nCurrent: {}\nNew: *{}*",
+                    markdown::escape(setting.label()),
+                    setting.get(&group),
+                    value
+                );
+                let _ = bot
+                    .edit_message_text(chat_id, message_id, text)
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .reply_markup(settings::edit_keyboard(&group_id, setting, value))
+                    .await;
+                return None;
+            }
+            SettingsAction::Edit(setting, value, true) => {
+                setting.set(&mut group, value);
+                notice = Some(self.update_settings(&group, format!("✅ {} set to {}.", setting.label(), setting.get(&group))).await);
+            }
+            SettingsAction::ToggleLapseAction => {
+                group.lapse_action = if group.lapse_action == LAPSE_ACTION_REMOVE { LAPSE_ACTION_RESTRICT } else { LAPSE_ACTION_REMOVE };
+                notice = Some(self.update_settings(&group, "✅ Lapse action updated.".to_owned()).await);
+            }
+            SettingsAction::ToggleJoinMode => {
+                group.join_mode = if group.join_mode == JOIN_MODE_REQUEST { JOIN_MODE_RESTRICT } else { JOIN_MODE_REQUEST };
+                let saved = if group.join_mode == JOIN_MODE_REQUEST {
+                    "✅ Join mode updated. Turn on \"Approve new members\" for the group's invite links."
+                } else {
+                    "✅ Join mode updated."
+                };
+                notice = Some(self.update_settings(&group, saved.to_owned()).await);
+            }
+            SettingsAction::Close => {
+                let _ = bot.edit_message_text(chat_id, message_id, "⚙️ Settings closed.").await;
+                return None;
+            }
+        }
+
+        let _ = bot
+            .edit_message_text(chat_id, message_id, self.render_settings(&group).await)
+            .parse_mode(ParseMode::MarkdownV2)
+            .reply_markup(settings::main_keyboard(&group))
+            .await;
+        notice
+    }
+
+    async fn update_settings(&self, group: &TelegramGroup, saved: String) -> String {
+        match self.tele_dao.update_group(group).await {
+            Ok(_) => saved,
+            Err(err) => format!("⚠️ Failed to update group settings: {}", err),
+        }
+    }
+
+    async fn save_group_settings(&self, bot: &Bot, chat: Chat, group: &TelegramGroup) {
+        match self.tele_dao.update_group(group).await {
+            Ok(_) => {
+                bot.send_message(chat.id, "✅ Group settings updated successfully\\.")
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await
+                    .unwrap();
+            }
+            Err(err) => {
+                let err_text = format!("⚠️ Failed to update group settings:\n`{}`", &err.to_string());
+                bot.send_message(chat.id, err_text)
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Show the requirements of a group the user is pending in, with the Mini App to verify for it
+    async fn start_verification(&self, bot: &Bot, chat: Chat, user: User, group_id: String) {
+        let group = self.tele_dao.get_group(group_id.clone()).await.unwrap_or(None);
+        let Some(group) = group.filter(|group| group.status == GROUP_STATUS_ACTIVE) else {
+            bot.send_message(chat.id, "🔴 This group is not gated by the bot.").await.unwrap();
+            return
+        };
+
+        let member = self.tele_dao.get_member(group_id.clone(), user.id.0 as i64).await.unwrap_or(None);
+        let reply = match member {
+            None => format!("🔴 You have not joined {} yet. Join it first, then verify.", group.name),
+            Some(member) if member.status == MEMBER_STATUS_ACCEPTED => format!("🟢 You are already verified in {}.", group.name),
+            Some(member) if member.status != MEMBER_STATUS_PENDING => format!("🔴 You have no pending verification in {}. Rejoin it to try again.", group.name),
+            Some(member) => {
+                bot.send_message(chat.id, format!("📋 Requirements of *{}*:{}", markdown::escape(&group.name), self.render_group_config(group.clone()).await))
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await
+                    .unwrap();
+                let mut instructions = bot.send_message(
+                    chat.id,
+                    format!(
+                        "Sign with your wallet in the app below within {}. The result will be reported here.",
+                        format_duration((member.expired - Utc::now().naive_utc()).max(Duration::zero()))
+                    ),
+                );
+                if let Some(keyboard) = verify_keyboard(&group_id, user.id.0 as i64) {
+                    instructions = instructions.reply_markup(keyboard);
+                }
+                instructions.await.unwrap();
+                return
+            }
+        };
+        bot.send_message(chat.id, reply).await.unwrap();
+    }
+
+    /// `https://t.me/<bot>?start=verify_<chat_id>`, opening the verification of the group in private
+    async fn verify_deep_link(&self, chat_id: &str) -> Option<reqwest::Url> {
+        let me = self.bot.get_me().await.ok()?;
+        let mut deep_link = me.tme_url();
+        deep_link.query_pairs_mut().append_pair("start", &format!("{}{}", START_VERIFY, chat_id));
+        Some(deep_link)
+    }
+
+    /// Run a group admin command sent in private, on the group id given first or the group picked from /mygroups
+    pub async fn handle_private_admin_command(&self, bot: &Bot, message: Message) {
+        let (Some(user), Some(text)) = (message.from.clone(), message.text()) else {
+            return
+        };
+        let chat = message.chat.clone();
+        let (name, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let args = args.trim_start();
+        let (group_id, args) = match args.split_once(char::is_whitespace).unwrap_or((args, "")) {
+            (group_id, rest) if group_id.starts_with('-') && group_id.parse::<i64>().is_ok() => (group_id, rest.trim_start()),
+            _ => ("", args),
+        };
+
+        let Ok(command) = CommandType::parse(format!("{} {}", name, args).trim_end(), "bot") else {
+            bot.send_message(chat.id, "🔴 Unknown command or invalid arguments, see /help.").await.unwrap();
+            return
+        };
+        if let CommandType::Help = command {
+            self.send_help_to_admin(bot.clone(), chat).await;
+            return
+        }
+
+        match self.resolve_admin_group(bot, user.id, group_id).await {
+            Ok(group) => self.run_admin_command(bot, chat, group, command).await,
+            Err(reply) => {
+                bot.send_message(chat.id, reply).await.unwrap();
+            }
+        }
+    }
+
+    /// The group an admin command sent in private applies to: the given group id, which then
+    /// becomes the picked one, or else the group last picked. Admin rights are re-checked live.
+    async fn resolve_admin_group(&self, bot: &Bot, user_id: UserId, group_id: &str) -> Result<TelegramGroup, String> {
+        let group = if group_id.is_empty() {
+            self.tele_dao.get_selected_group(user_id.0 as i64).await.unwrap_or(None)
+                .ok_or("📋 Pick a group with /mygroups first, or pass its id before the arguments, e.g. /setamount (group_id) (amount).".to_owned())?
+        } else {
+            self.tele_dao.get_group(group_id.to_owned()).await.unwrap_or(None)
+                .ok_or("❌ You are not an admin of this group.".to_owned())?
+        };
+
+        if !self.is_group_admin(bot, &group.chat_id, user_id).await {
+            return Err("❌ You are not an admin of this group.".to_owned());
+        }
+        if !group_id.is_empty() {
+            let _ = self.tele_dao.select_admin_group(group.chat_id.clone(), user_id.0 as i64).await;
+        }
+        Ok(group)
     }
 
     pub async fn handle_private_command(&self, bot: &Bot, message: Message, command: PrivateCommandType) {
         let chat = message.chat.clone();
-        if let Some(user) = message.from {
+        if let Some(user) = message.from.clone() {
             match command {
                 PrivateCommandType::MyGroups => {
                     let groups: Vec<TelegramGroup> = self.tele_dao.get_group_by_admin(user.id.0 as i64).await.unwrap_or(vec![]);
+                    let buttons = groups.iter().map(|group| {
+                        vec![InlineKeyboardButton::callback(format!("Manage {}", group.name), format!("{}{}", CALLBACK_SELECT_GROUP, group.chat_id))]
+                    }).collect::<Vec<_>>();
                     let mut table = String::from("<pre>\n");
                     table.push_str("+-----------------+----------------------+\n");
                     table.push_str("| GroupId         | Name                 |\n");
@@ -310,29 +1115,179 @@ impl TelegramService {
                     }
                     table.push_str("+-----------------+----------------------+\n");
                     table.push_str("</pre>");
+                    table.push_str("\nPick a group to manage: admin commands sent here then apply to it.");
                     bot.send_message(chat.id, table)
                     .parse_mode(ParseMode::Html)
+                    .reply_markup(InlineKeyboardMarkup::new(buttons))
                     .await
                     .unwrap();
                 }
                 PrivateCommandType::GroupConfig(group_id) => {
-                    self.send_group_config_to_admin(bot.clone(), group_id, chat).await;
+                    match self.resolve_admin_group(bot, user.id, group_id.trim()).await {
+                        Ok(group) => self.send_group_config_to_admin(bot.clone(), group.chat_id, chat).await,
+                        Err(reply) => {
+                            bot.send_message(chat.id, reply).await.unwrap();
+                        }
+                    }
                 }
                 PrivateCommandType::ListUsers(group_id) => {
-                    self.send_list_users_to_admin(bot.clone(), group_id, chat).await;
+                    match self.resolve_admin_group(bot, user.id, group_id.trim()).await {
+                        Ok(group) => self.send_list_users_to_admin(bot.clone(), group.chat_id, chat).await,
+                        Err(reply) => {
+                            bot.send_message(chat.id, reply).await.unwrap();
+                        }
+                    }
+                }
+                PrivateCommandType::UploadList(args) => {
+                    let args = args.split_whitespace().collect::<Vec<&str>>();
+                    let root_only = args.last() == Some(&"root");
+                    let group_id = args.first().filter(|arg| **arg != "root").copied().unwrap_or_default();
+                    let group_id = match self.resolve_admin_group(bot, user.id, group_id).await {
+                        Ok(group) => group.chat_id,
+                        Err(reply) => {
+                            bot.send_message(chat.id, reply).await.unwrap();
+                            return
+                        }
+                    };
+
+                    let Some(document) = message.document() else {
+                        bot.send_message(chat.id, "🔴 Please attach a CSV file of CKB addresses, with `/uploadlist (group_id) [root]` as its caption.")
+                            .await
+                            .unwrap();
+                        return
+                    };
+
+                    let mut data: Vec<u8> = vec![];
+                    let downloaded = match bot.get_file(document.file.id.clone()).await {
+                        Ok(file) => bot.download_file(&file.path, &mut data).await.is_ok(),
+                        Err(_) => false,
+                    };
+                    if !downloaded {
+                        bot.send_message(chat.id, "🔴 Could not download the file, please try again.").await.unwrap();
+                        return
+                    }
+
+                    let addresses = RuleSrv::parse_address_list(&String::from_utf8_lossy(&data));
+                    let count = addresses.len();
+                    let reply = match self.rule_srv.set_address_list(group_id, addresses, root_only).await {
+                        Ok(rule) => format!(
+                            "🟢 Address list saved: {} addresses.\nMerkle root: {}",
+                            count,
+                            rule.merkle_root.unwrap_or_default()
+                        ),
+                        Err(err) => format!("🔴 Save address list failed: {}", err),
+                    };
+                    bot.send_message(chat.id, reply).await.unwrap();
+                }
+                PrivateCommandType::Start(payload) => {
+                    match payload.strip_prefix(START_VERIFY) {
+                        Some(group_id) => self.start_verification(bot, chat, user, group_id.to_owned()).await,
+                        None => {
+                            bot.send_message(chat.id, "👋 Hi! Use the Verify button in a group gated by this bot to get started.").await.unwrap();
+                        }
+                    }
+                }
+                PrivateCommandType::Settings(group_id) => {
+                    let groups = self.tele_dao.get_group_by_admin(user.id.0 as i64).await.unwrap_or_default();
+                    let group_id = group_id.trim();
+                    if group_id.is_empty() {
+                        let rows = groups
+                            .iter()
+                            .map(|group| vec![InlineKeyboardButton::callback(group.name.clone(), settings::encode(&group.chat_id, &SettingsAction::Main))])
+                            .collect::<Vec<_>>();
+                        bot.send_message(chat.id, "⚙️ Pick a group to configure:")
+                            .reply_markup(InlineKeyboardMarkup::new(rows))
+                            .await
+                            .unwrap();
+                        return
+                    }
+
+                    match self.resolve_admin_group(bot, user.id, group_id).await {
+                        Ok(group) => self.send_settings_panel(bot, chat.id, group).await,
+                        Err(reply) => {
+                            bot.send_message(chat.id, reply).await.unwrap();
+                        }
+                    }
+                }
+                PrivateCommandType::Refresh => {
+                    let reply = self.refresh_user_balances(user.id.0 as i64).await;
+                    bot.send_message(chat.id, reply).await.unwrap();
                 }
             }   
         }
     }
 
+    /// Re-fetch the balances of every wallet the user verified with, skipping the cache, and
+    /// re-check their memberships right away
+    async fn refresh_user_balances(&self, user_id: i64) -> String {
+        let memberships = self.tele_dao.get_group_by_user_id(user_id, None).await.unwrap_or_default();
+        let mut balances_by_address: HashMap<(String, Option<String>), BalanceResult> = HashMap::new();
+        for member in memberships.iter() {
+            let Some(ckb_address) = member.ckb_address.clone() else {
+                continue;
+            };
+            if let Entry::Vacant(entry) = balances_by_address.entry((ckb_address.clone(), member.btc_address.clone())) {
+                entry.insert(get_member_balances(ckb_address, member.btc_address.clone(), true).await);
+            }
+        }
+
+        if balances_by_address.is_empty() {
+            return "❌ You have not verified a wallet yet.".to_owned();
+        }
+        if let Some(Err(err)) = balances_by_address.values().find(|balances| balances.is_err()) {
+            return format!("🔴 Balance lookup failed, please try again later: {}", err);
+        }
+
+        for member in memberships {
+            if member.status != MEMBER_STATUS_ACCEPTED && member.status != MEMBER_STATUS_LAPSED {
+                continue;
+            }
+            let Some(ckb_address) = member.ckb_address.clone() else {
+                continue;
+            };
+            let Ok(Some(group)) = self.tele_dao.get_group(member.chat_id.clone()).await else {
+                continue;
+            };
+            if self.is_member_exempt(&member).await {
+                if member.status == MEMBER_STATUS_LAPSED || member.warned_at.is_some() {
+                    self.accept_exempt_member(member).await;
+                }
+                continue;
+            }
+            if let Some(Ok(balances)) = balances_by_address.get(&(ckb_address, member.btc_address.clone())) {
+                let balances = balances.clone();
+                self.reverify_member(&group, member, &balances).await;
+            }
+        }
+
+        "🟢 Balances refreshed. If you are still waiting to join a group, verify again now.".to_owned()
+    }
+
+    async fn render_balances(&self, balances: &serde_json::Value) -> String {
+        let Some(balances) = balances.as_object().filter(|balances| !balances.is_empty()) else {
+            return "This address holds no tokens.".to_owned();
+        };
+
+        let mut text = String::from("💰 Balances:\n");
+        for (type_hash, amount) in balances {
+            let name = match self.token_srv.get_token(type_hash.clone()).await {
+                Some(token) => token.symbol.or(token.name).unwrap_or(type_hash.clone()),
+                None => type_hash.clone(),
+            };
+            text.push_str(&format!("• {}: {}\n", name, amount));
+        }
+        text
+    }
+
     pub async fn send_group_config_to_admin(&self, bot: Bot, group_id: String, chat: Chat) {
         if let Some(group) = self.tele_dao.get_group(group_id.clone()).await.unwrap() {
 
             let mut table = self.render_group_config(group.clone()).await;
             let members: Vec<TelegramGroupJoined> = self.tele_dao.get_member_by_group(group_id.clone()).await.unwrap_or(vec![]);
+            let present_count = members.iter().filter(|m| is_member_present(m.status)).count();
             let accepted_count = members.iter().filter(|m| m.status == MEMBER_STATUS_ACCEPTED).count();
             
-            table.push_str(&format!("👥 Verification Status: {}/{} members verified", accepted_count, members.len()));
+            table.push_str(&format!("👥 Verification Status: {}/{} members verified", accepted_count, present_count));
 
             bot.send_message(chat.id, table)
             .parse_mode(ParseMode::MarkdownV2)
@@ -344,20 +1299,33 @@ impl TelegramService {
     pub async fn send_list_users_to_admin(&self, bot: Bot, group_id: String, chat: Chat) {
         if self.tele_dao.get_group(group_id.clone()).await.unwrap().is_some(){
             let members: Vec<TelegramGroupJoined> = self.tele_dao.get_member_by_group(group_id.clone()).await.unwrap_or(vec![]);
-            let accepted_count = members.iter().filter(|m| m.status == MEMBER_STATUS_ACCEPTED).count();
+            let (present, former): (Vec<_>, Vec<_>) = members.iter().partition(|m| is_member_present(m.status));
+            let accepted_count = present.iter().filter(|m| m.status == MEMBER_STATUS_ACCEPTED).count();
             
-            let mut table = format!("👥 Verification Status: {}/{} members verified\n\n", accepted_count, members.len());
-            if members.is_empty() {
-                table.push_str("No one has joined this group.")
+            let mut table = format!("👥 Verification Status: {}/{} members verified\n\n", accepted_count, present.len());
+            if present.is_empty() {
+                table.push_str("No one has joined this group.\n")
             }
             
-            for (idx, member) in members.iter().enumerate() {
-                if member.status == MEMBER_STATUS_ACCEPTED {
-                    table.push_str(&format!("{}. @{} (auth: true)\n", idx+1, member.user_name));
-                } else {
-                    table.push_str(&format!("{}. @{} (❌ Not verified yet)\n", idx+1, member.user_name));
+            for (idx, member) in present.iter().enumerate() {
+                let state = match member.status {
+                    MEMBER_STATUS_ACCEPTED => "auth: true",
+                    MEMBER_STATUS_LAPSED => "⚠️ Below requirements",
+                    _ => "❌ Not verified yet",
+                };
+                table.push_str(&format!("{}. @{} ({})\n", idx+1, member.user_name, state));
+            }
+
+            if !former.is_empty() {
+                table.push_str("\n🚪 No longer in the group:\n");
+                for member in former {
+                    let state = match member.status {
+                        MEMBER_STATUS_BANNED => "banned",
+                        MEMBER_STATUS_KICKED => "removed",
+                        _ => "left",
+                    };
+                    table.push_str(&format!("• @{} ({})\n", member.user_name, state));
                 }
-                
             }
             bot.send_message(chat.id, table)
             .parse_mode(ParseMode::Html)
@@ -366,14 +1334,70 @@ impl TelegramService {
         }
     }
 
+    async fn add_group_rule(&self, bot: Bot, chat: Chat, rule: GroupRule) {
+        let reply = match self.rule_srv.add_rule(rule).await {
+            Ok(rule) => format!("🟢 Rule {} added: {}", rule.id, RuleSrv::describe(&rule)),
+            Err(err) => format!("🔴 Add rule failed: {}", err),
+        };
+        bot.send_message(chat.id, reply).await.unwrap();
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn add_evm_rule(&self, bot: Bot, chat: Chat, chat_id: String, rule_type: i16, chain_id: i64, contract: String, amount: i64) {
+        if get_evm_rpc(chain_id).is_none() {
+            bot.send_message(chat.id, format!("🔴 Add rule failed: no RPC configured for chain {}", chain_id))
+                .await
+                .unwrap();
+            return
+        }
+        if contract.parse::<ethers::types::Address>().is_err() {
+            bot.send_message(chat.id, "🔴 Add rule failed: invalid contract address")
+                .await
+                .unwrap();
+            return
+        }
+
+        self.add_group_rule(bot, chat, GroupRule {
+            token_address: Some(contract.to_lowercase()),
+            min_amount: Some(amount),
+            chain_id: Some(chain_id),
+            ..GroupRule::new(chat_id, rule_type)
+        }).await;
+    }
+
     pub async fn send_help_to_admin(&self, bot: Bot, chat: Chat) {
         let mut table = String::from("*👤 Admin Commands:*\n\n");
-        table.push_str("1\\. `/settoken (type_script_hash|ckb)`: Set the gated token\n");
+        table.push_str("1\\. `/settoken (type_script_hash|symbol|name|ckb)`: Set the gated token, picking from matches when the name is ambiguous\n");
         table.push_str("2\\. `/setamount (amount)`: Set minimum required balance\n");
         table.push_str("3\\. `/setage (age)`: Set minimum required age \\(years\\)\n");
-        table.push_str("4\\. `/groupconfig`: View current group settings\n");
-        table.push_str("5\\. `/listusers`: List currently verified users\n");
-        table.push_str("6\\. `/mygroups`: Bot status: list groups the bot manages\n");
+        table.push_str("4\\. `/addholdingrule (type_script_hash|ckb) (amount) (days)`: Require holding a token for a number of days\n");
+        table.push_str("5\\. `/addtxrule (count)`: Require a minimum number of on\\-chain transactions\n");
+        table.push_str("6\\. `/addagerule (days)`: Require an address older than a number of days\n");
+        table.push_str("7\\. `/adderc20rule (chain_id) (contract) (amount)`: Require an ERC\\-20 balance on an EVM chain\n");
+        table.push_str("8\\. `/adderc721rule (chain_id) (contract) (count)`: Require owning ERC\\-721 tokens on an EVM chain\n");
+        table.push_str("9\\. `/adddidrule [namespace.bit]`: Require owning a \\.bit account, or a sub\\-account of the namespace\n");
+        table.push_str("10\\. `/listrules`: List the group rules\n");
+        table.push_str("11\\. `/delrule (id)`: Remove a group rule\n");
+        table.push_str("12\\. `/addtier (name) (type_script_hash|ckb) (amount) (text,media,previews,polls)`: Add a membership tier with its permissions, all tiers on the same token\n");
+        table.push_str("13\\. `/listtiers`: List the membership tiers\n");
+        table.push_str("14\\. `/deltier (id)`: Remove a membership tier\n");
+        table.push_str("15\\. `/setgrace (hours)`: Set how long members below the requirements have to top up\n");
+        table.push_str("16\\. `/setlapseaction (restrict|remove)`: Set what happens once the grace period ends\n");
+        table.push_str("17\\. `/setjoinmode (restrict|request)`: Restrict new members until verified, or approve their join requests once verified\n");
+        table.push_str("18\\. `/setkyctime (minutes)`: Set how long new members have to verify\n");
+        table.push_str("19\\. `/setbantime (minutes)`: Set the ban cooldown, doubled for every repeated failure\n");
+        table.push_str("20\\. `/exempt (tgid|@username|ckb_address)`: Exempt a member from gating\n");
+        table.push_str("21\\. `/unexempt (tgid|@username|ckb_address)`: Remove an exemption\n");
+        table.push_str("22\\. `/groupconfig`: View current group settings\n");
+        table.push_str("23\\. `/settings`: Open the settings panel, also in private chat as `/settings [group_id]`\n");
+        table.push_str("24\\. `/listusers`: List currently verified users\n");
+        table.push_str("25\\. `/checkbalance (ckb_address)`: Show the balances of an address, as cached for the checks\n");
+        table.push_str("26\\. `/mygroups`: In private chat, list the groups you manage and pick one to administer\n");
+        table.push_str("\n*💬 In Private Chat:*\n\n");
+        table.push_str("Pick a group with `/mygroups`, or pass its id first, e\\.g\\. `/setamount (group_id) (amount)`\\. Every admin command above then works here, keeping changes out of the group\\.\n");
+        table.push_str("\n*👥 Member Commands:*\n\n");
+        table.push_str("1\\. `/refresh`: In private chat, re\\-fetch your balances right after topping up\n");
+        table.push_str("2\\. `/start verify_(group_id)`: In private chat, show the group requirements and verify with the Mini App\n");
         
         bot.send_message(chat.id, table)
         .parse_mode(ParseMode::MarkdownV2)
@@ -391,6 +1415,11 @@ impl TelegramService {
                 token_address: None, 
                 min_approve_balance: Some(0), 
                 min_approve_age: Some(18), 
+                grace_hours: Some(24),
+                lapse_action: LAPSE_ACTION_RESTRICT,
+                kyc_minutes: Some(DEFAULT_KYC_MINUTES),
+                ban_minutes: Some(DEFAULT_BAN_MINUTES),
+                join_mode: JOIN_MODE_RESTRICT,
                 created_at: Utc::now().naive_utc(), 
                 updated_at: Utc::now().naive_utc() }).await {
                     return Some(group);
@@ -401,24 +1430,66 @@ impl TelegramService {
     }
     
     pub async fn is_admin(&self, message: Message, bot: &Bot) -> bool {
-        if let Some(user) = message.from {
-            match bot.get_chat_member(message.chat.id, user.id).send().await {
-                Ok(member) => {
-                    return matches!(
-                        member.status(),
-                        ChatMemberStatus::Administrator | ChatMemberStatus::Owner
-                    )
-                }
-                Err(_) => return false,
-            }
+        match message.from {
+            Some(user) => self.is_chat_admin(bot, message.chat.id, user.id).await,
+            None => false,
+        }
+    }
+
+    /// Whether the user is a recorded admin of the group and still one right now
+    pub async fn is_group_admin(&self, bot: &Bot, group_id: &str, user_id: UserId) -> bool {
+        let Ok(chat_id) = group_id.parse::<i64>() else {
+            return false;
+        };
+        let is_recorded = self.tele_dao.get_group_by_admin(user_id.0 as i64).await
+            .unwrap_or_default()
+            .iter()
+            .any(|group| group.chat_id == group_id);
+        is_recorded && self.is_chat_admin(bot, ChatId(chat_id), user_id).await
+    }
+
+    pub async fn is_chat_admin(&self, bot: &Bot, chat_id: ChatId, user_id: UserId) -> bool {
+        match bot.get_chat_member(chat_id, user_id).send().await {
+            Ok(member) => matches!(
+                member.status(),
+                ChatMemberStatus::Administrator | ChatMemberStatus::Owner
+            ),
+            Err(_) => false,
         }
-        false
     }
 
     pub async fn cron_auto_kick_member(&self) {
         if let Ok(members) = self.tele_dao.get_member_not_kyc().await {
-            let until_date = Utc::now() + MEMBER_BAN_DURATION;
+            let mut groups: HashMap<String, Option<TelegramGroup>> = HashMap::new();
             for member in members {
+                if !groups.contains_key(&member.chat_id) {
+                    let group = self.tele_dao.get_group(member.chat_id.clone()).await.unwrap_or(None);
+                    groups.insert(member.chat_id.clone(), group);
+                }
+                let Some(group) = groups[&member.chat_id].clone().filter(|group| group.status == GROUP_STATUS_ACTIVE) else {
+                    continue;
+                };
+
+                if self.is_member_exempt(&member).await {
+                    self.accept_exempt_member(member).await;
+                    continue;
+                }
+
+                // Applicants are not in the chat yet, their request is declined instead
+                if member.via_request {
+                    self.decline_expired_request(&group, member).await;
+                    continue;
+                }
+
+                // Leaves missed while the bot was offline are only noticed here
+                if !self.is_in_chat(&member).await {
+                    let _ = self.tele_dao.update_member_status(member.chat_id.clone(), member.user_id, MEMBER_STATUS_LEFT).await;
+                    continue;
+                }
+
+                let fail_count = self.tele_dao.record_member_failure(member.chat_id.clone(), member.user_id).await.unwrap_or(1);
+                let ban_duration = group.ban_duration(fail_count);
+                let until_date = Utc::now() + ban_duration;
                 let _ = self.bot
                         .ban_chat_member(
                             member.clone().chat_id.to_string(),
@@ -431,9 +1502,11 @@ impl TelegramService {
                             member.clone().chat_id.to_string(),
                             format!(
                                 "🔴 **{}** failed verification and was removed.\n\
-                                _Reason:_ didn’t complete verification within 5 minutes.\n\
-                                They can rejoin and try again after the 15‑minute cooldown.",
+                                _Reason:_ didn’t complete verification within {}.\n\
+                                They can rejoin and try again after the {} cooldown.",
                                 member.clone().user_name,
+                                format_duration(group.kyc_duration()),
+                                format_duration(ban_duration),
                             ),
                         )
                         .await;
@@ -446,63 +1519,279 @@ impl TelegramService {
         }
     }
 
-    pub async fn fetch_token(&self, type_hash: String) -> Option<Token>{
-        let type_hash = type_hash.to_lowercase();
+    pub async fn cron_reverify_members(&self) {
+        let interval_minutes: i64 = config::get("reverify_interval_minutes");
         let now = Utc::now().naive_utc();
-        if type_hash.is_empty() || type_hash == "ckb" {
-            return Some(Token::ckb(now));
+        if let Ok(members) = self.tele_dao.get_members_to_reverify(now - Duration::minutes(interval_minutes)).await {
+            let mut groups: HashMap<String, Option<TelegramGroup>> = HashMap::new();
+            let mut balances_by_address: HashMap<(String, Option<String>), BalanceResult> = HashMap::new();
+            for member in members {
+                if !groups.contains_key(&member.chat_id) {
+                    let group = self.tele_dao.get_group(member.chat_id.clone()).await.unwrap_or(None);
+                    groups.insert(member.chat_id.clone(), group);
+                }
+                let Some(group) = groups[&member.chat_id].clone().filter(|group| group.status == GROUP_STATUS_ACTIVE) else {
+                    continue;
+                };
+
+                if self.is_member_exempt(&member).await {
+                    if member.status == MEMBER_STATUS_LAPSED || member.warned_at.is_some() {
+                        self.accept_exempt_member(member).await;
+                    }
+                    continue;
+                }
+
+                let ckb_address = member.ckb_address.clone().unwrap_or_default();
+                let wallets = (ckb_address.clone(), member.btc_address.clone());
+                if !balances_by_address.contains_key(&wallets) {
+                    let balances = get_member_balances(ckb_address.clone(), member.btc_address.clone(), false).await;
+                    balances_by_address.insert(wallets.clone(), balances);
+                }
+                // Unknown balances are retried on the next run rather than read as zero
+                let balances = match &balances_by_address[&wallets] {
+                    Ok(balances) => balances,
+                    Err(err) => {
+                        println!("Balance lookup failed for {}: {}", ckb_address, err);
+                        continue;
+                    }
+                };
+
+                self.reverify_member(&group, member, balances).await;
+            }
         }
+    }
+
+    /// Move accepted members of tiered groups to the tier their balances qualify for. This runs on
+    /// every cron run, while the full re-verification waits for `reverify_interval_minutes`.
+    pub async fn cron_update_member_tiers(&self) {
+        if let Ok(members) = self.tele_dao.get_members_in_tiered_groups(MEMBER_STATUS_ACCEPTED).await {
+            let mut groups: HashMap<String, Option<TelegramGroup>> = HashMap::new();
+            let mut balances_by_address: HashMap<(String, Option<String>), BalanceResult> = HashMap::new();
+            for member in members {
+                if !groups.contains_key(&member.chat_id) {
+                    let group = self.tele_dao.get_group(member.chat_id.clone()).await.unwrap_or(None);
+                    groups.insert(member.chat_id.clone(), group);
+                }
+                if groups[&member.chat_id].as_ref().is_none_or(|group| group.status != GROUP_STATUS_ACTIVE) {
+                    continue;
+                }
+                if self.is_member_exempt(&member).await {
+                    continue;
+                }
 
-        if let Ok(Some(token)) = self.token_dao.get_token(type_hash.clone()).await {
-            return Some(token);
+                let ckb_address = member.ckb_address.clone().unwrap_or_default();
+                let wallets = (ckb_address.clone(), member.btc_address.clone());
+                if !balances_by_address.contains_key(&wallets) {
+                    let balances = get_member_balances(ckb_address, member.btc_address.clone(), false).await;
+                    balances_by_address.insert(wallets.clone(), balances);
+                }
+                if let Ok(balances) = &balances_by_address[&wallets] {
+                    self.apply_member_tier(&member, balances).await;
+                }
+            }
+        }
+    }
+
+    /// Give the member the permissions of the tier their balances qualify for, if it changed.
+    /// Returns false when Telegram refused the new permissions.
+    async fn apply_member_tier(&self, member: &TelegramGroupJoined, balances: &serde_json::Value) -> bool {
+        let (tier, permissions) = self.tier_srv.resolve_permissions(member.chat_id.clone(), balances).await;
+        let tier_id = tier.as_ref().map(|tier| tier.id);
+        if tier_id == member.tier_id {
+            return true;
         }
 
-        self.fetch_and_store(type_hash, now).await
+        if self.bot
+            .restrict_chat_member(member.chat_id.clone(), UserId(member.user_id as u64), permissions)
+            .await
+            .is_err()
+        {
+            return false;
+        }
+
+        let tier_name = tier.map(|tier| tier.name).unwrap_or("member".to_owned());
+        let text = format!("🏅 **{}** is now in the **{}** tier.", member.user_name, tier_name);
+        let _ = self.bot.send_message(member.chat_id.clone(), text).await;
+        let _ = self.tele_dao.update_member_tier(member.chat_id.clone(), member.user_id, tier_id).await;
+        true
     }
 
-    async fn fetch_and_store(&self, type_hash: String, now: NaiveDateTime) -> Option<Token> {
-        // Try XUDT
-        if let Some(info) = get_xudt_info(type_hash.clone()).await {
-            if let Some(ts) = info.type_script {
-                let tok = Token {
-                    type_hash:   type_hash.clone(),
-                    name:        info.full_name,
-                    symbol:      info.symbol,
-                    decimal:     info.decimal,
-                    description: info.description,
-                    token_type:  TOKEN_TYPE_XUDT,
-                    args:        ts.args.unwrap_or("".to_owned()),
-                    code_hash:   ts.code_hash.unwrap_or("".to_owned()),
-                    hash_type:   ts.hash_type.unwrap_or("".to_owned()),
-                    created_at:  now,
-                    updated_at:  now,
-                };
-                let _ = self.token_dao.add_token(tok.clone()).await;
-                return Some(tok);
-            }
-        }
-
-        // Fallback to collection 
-        if let Some(info) = get_collection_info(type_hash.clone()).await {
-            let ts = info.type_script;
-            let tok = Token {
-                type_hash:   type_hash.clone(),
-                name:        Some(info.name),
-                symbol:      Some(String::new()),
-                decimal:     Some(String::new()),
-                description: Some(info.standard),
-                token_type:  TOKEN_TYPE_SPORE,
-                args:        ts.args,
-                code_hash:   ts.code_hash,
-                hash_type:   ts.hash_type,
-                created_at:  now,
-                updated_at:  now,
-            };
-            let _ = self.token_dao.add_token(tok.clone()).await;
-            return Some(tok);
+    /// Re-check an accepted or lapsed member against the group's current requirements
+    async fn reverify_member(&self, group: &TelegramGroup, member: TelegramGroupJoined, balances: &serde_json::Value) {
+        let ckb_address = member.ckb_address.clone().unwrap_or_default();
+        match self.rule_srv.check_group(group, ckb_address, member.evm_address.clone(), balances, None).await {
+            Ok(Ok(())) => self.reinstate_member(member, balances).await,
+            Ok(Err(reason)) => self.lapse_member(group, member, balances, reason).await,
+            // The member stays as they are until a later run can tell
+            Err(err) => println!("Re-verification of {} in {} failed: {}", member.user_name, group.name, err),
+        }
+    }
+
+    async fn decline_expired_request(&self, group: &TelegramGroup, member: TelegramGroupJoined) {
+        let fail_count = self.tele_dao.record_member_failure(member.chat_id.clone(), member.user_id).await.unwrap_or(1);
+        let ban_duration = group.ban_duration(fail_count);
+        let _ = self.bot
+            .decline_chat_join_request(member.chat_id.clone(), UserId(member.user_id as u64))
+            .await;
+        // Banning a non-member keeps them from sending another request until it ends
+        let _ = self.bot
+            .ban_chat_member(member.chat_id.clone(), UserId(member.user_id as u64))
+            .until_date(Utc::now() + ban_duration)
+            .await;
+        let _ = self.bot
+            .send_message(
+                ChatId(member.user_id),
+                format!(
+                    "🔴 Your request to join **{}** was declined.\n\
+                    _Reason:_ didn’t complete verification within {}.\n\
+                    You can request again in {}.",
+                    group.name,
+                    format_duration(group.kyc_duration()),
+                    format_duration(ban_duration),
+                ),
+            )
+            .await;
+        let _ = self
+            .tele_dao
+            .update_member(None, None, member.chat_id, member.user_id, member.expired, MEMBER_STATUS_REJECT, member.balances.unwrap_or("{}".to_owned()))
+            .await;
+    }
+
+    async fn is_in_chat(&self, member: &TelegramGroupJoined) -> bool {
+        match self.bot.get_chat_member(member.chat_id.clone(), UserId(member.user_id as u64)).await {
+            Ok(chat_member) => chat_member.kind.is_present(),
+            // Unknown membership is treated as present, as before
+            Err(_) => true,
+        }
+    }
+
+    async fn is_member_exempt(&self, member: &TelegramGroupJoined) -> bool {
+        self.exemption_dao
+            .is_exempt(member.chat_id.clone(), member.user_id, Some(member.user_name.clone()), member.ckb_address.clone())
+            .await
+            .unwrap_or(false)
+    }
+
+    /// Give an exempted member full access without verification
+    async fn accept_exempt_member(&self, member: TelegramGroupJoined) {
+        if member.via_request {
+            let _ = self.bot
+                .approve_chat_join_request(member.chat_id.clone(), UserId(member.user_id as u64))
+                .await;
+        }
+        let _ = self.bot
+            .restrict_chat_member(member.chat_id.clone(), UserId(member.user_id as u64), ChatPermissions::all())
+            .await;
+
+        if let Ok(None) = self.tele_dao.get_member(member.chat_id.clone(), member.user_id).await {
+            let _ = self.tele_dao.add_member(member.clone()).await;
+        }
+
+        let _ = self
+            .tele_dao
+            .update_member_check(member.chat_id, member.user_id, MEMBER_STATUS_ACCEPTED, member.balances.unwrap_or("{}".to_owned()), Utc::now().naive_utc(), None)
+            .await;
+    }
+
+    /// Apply the member's current tier, restoring access if it had lapsed
+    async fn reinstate_member(&self, member: TelegramGroupJoined, balances: &serde_json::Value) {
+        if member.status == MEMBER_STATUS_LAPSED {
+            let (tier, permissions) = self.tier_srv.resolve_permissions(member.chat_id.clone(), balances).await;
+            if self.bot
+                .restrict_chat_member(member.chat_id.clone(), UserId(member.user_id as u64), permissions)
+                .await
+                .is_err()
+            {
+                return;
+            }
+
+            let text = format!("🟢 **{}** meets the group requirements again — access restored.", member.user_name);
+            let _ = self.bot.send_message(member.chat_id.clone(), text).await;
+            let _ = self.tele_dao.update_member_tier(member.chat_id.clone(), member.user_id, tier.map(|tier| tier.id)).await;
+        } else if !self.apply_member_tier(&member, balances).await {
+            return;
+        }
+
+        let _ = self
+            .tele_dao
+            .update_member_check(member.chat_id, member.user_id, MEMBER_STATUS_ACCEPTED, balances.to_string(), Utc::now().naive_utc(), None)
+            .await;
+    }
+
+    /// Warn a member who fell below the group requirements, then restrict or remove them once
+    /// the group's grace period has passed
+    async fn lapse_member(&self, group: &TelegramGroup, member: TelegramGroupJoined, balances: &serde_json::Value, reason: String) {
+        let now = Utc::now().naive_utc();
+        let grace_hours = group.grace_hours.unwrap_or(0);
+        let mut status = member.status;
+        let mut warned_at = member.warned_at;
+
+        match member.warned_at {
+            _ if member.status == MEMBER_STATUS_LAPSED => {}
+            None => {
+                let _ = self.bot
+                    .send_message(
+                        member.chat_id.clone(),
+                        format!(
+                            "⚠️ **{}** no longer meets the group requirements.\n\
+                            _Reason:_ {}.\n\
+                            Please top up within {} hours to keep access.",
+                            member.user_name,
+                            reason,
+                            grace_hours,
+                        ),
+                    )
+                    .await;
+                warned_at = Some(now);
+            }
+            Some(warned) if now >= warned + Duration::hours(grace_hours as i64) => {
+                if group.lapse_action == LAPSE_ACTION_REMOVE {
+                    let fail_count = self.tele_dao.record_member_failure(member.chat_id.clone(), member.user_id).await.unwrap_or(1);
+                    let ban_duration = group.ban_duration(fail_count);
+                    let until_date = Utc::now() + ban_duration;
+                    let _ = self.bot
+                        .ban_chat_member(member.chat_id.clone(), UserId(member.user_id as u64))
+                        .until_date(until_date)
+                        .await;
+                    let _ = self.bot
+                        .send_message(
+                            member.chat_id.clone(),
+                            format!(
+                                "🔴 **{}** no longer meets the group requirements and was removed.\n\
+                                _Reason:_ {}.\n\
+                                They can rejoin and try again after the {} cooldown.",
+                                member.user_name,
+                                reason,
+                                format_duration(ban_duration),
+                            ),
+                        )
+                        .await;
+                    status = MEMBER_STATUS_REJECT;
+                } else {
+                    let _ = self.bot
+                        .restrict_chat_member(member.chat_id.clone(), UserId(member.user_id as u64), ChatPermissions::empty())
+                        .await;
+                    let _ = self.bot
+                        .send_message(
+                            member.chat_id.clone(),
+                            format!(
+                                "🔴 **{}** no longer meets the group requirements and was restricted.\n\
+                                _Reason:_ {}.\n\
+                                Access is restored automatically once the requirements are met again.",
+                                member.user_name,
+                                reason,
+                            ),
+                        )
+                        .await;
+                    status = MEMBER_STATUS_LAPSED;
+                }
+            }
+            Some(_) => {}
         }
 
-        // Nothing found
-        None
+        let _ = self
+            .tele_dao
+            .update_member_check(member.chat_id, member.user_id, status, balances.to_string(), now, warned_at)
+            .await;
     }
 }
@@ -0,0 +1,120 @@
+use serde_json::Value;
+use teloxide::types::ChatPermissions;
+
+use crate::{
+    models::tier::{
+        GroupTier, TIER_PERMISSION_MEDIA, TIER_PERMISSION_POLLS, TIER_PERMISSION_PREVIEWS,
+        TIER_PERMISSION_TEXT,
+    },
+    repositories::tier::TierDao,
+    serialize::error::AppError,
+    services::rule::balance_key,
+};
+
+#[derive(Clone, Debug)]
+pub struct TierSrv {
+    tier_dao: TierDao,
+}
+
+impl TierSrv {
+    pub fn new(tier_dao: TierDao) -> Self {
+        TierSrv {
+            tier_dao: tier_dao.clone(),
+        }
+    }
+
+    /// Add a tier. All tiers of a group gate on one token, as balances of different tokens
+    /// cannot be ranked against each other.
+    pub async fn add_tier(&self, tier: GroupTier) -> Result<GroupTier, AppError> {
+        let tiers = self.get_tiers(tier.chat_id.clone()).await;
+        if tiers
+            .iter()
+            .any(|other| tier_token(other) != tier_token(&tier))
+        {
+            return Err(AppError::new(400).message("all tiers of a group must use the same token"));
+        }
+
+        self.tier_dao
+            .add_tier(tier)
+            .await
+            .map_err(|e| AppError::new(500).cause(e).message("add tier failed"))
+    }
+
+    pub async fn delete_tier(&self, chat_id: String, id: i32) -> Result<bool, AppError> {
+        self.tier_dao
+            .delete_tier(chat_id, id)
+            .await
+            .map_err(|e| AppError::new(500).cause(e).message("delete tier failed"))
+    }
+
+    pub async fn get_tiers(&self, chat_id: String) -> Vec<GroupTier> {
+        self.tier_dao
+            .get_tiers_by_group(chat_id)
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Pick the highest tier the balances qualify for, together with the permissions to grant.
+    ///
+    /// Groups without tiers grant full permissions; members of tiered groups that qualify for
+    /// no tier may only send text.
+    pub async fn resolve_permissions(
+        &self,
+        chat_id: String,
+        balances: &Value,
+    ) -> (Option<GroupTier>, ChatPermissions) {
+        let tiers = self.get_tiers(chat_id).await;
+        if tiers.is_empty() {
+            return (None, ChatPermissions::all());
+        }
+
+        match tiers.into_iter().find(|tier| qualifies(tier, balances)) {
+            Some(tier) => {
+                let permissions = chat_permissions(tier.permissions);
+                (Some(tier), permissions)
+            }
+            None => (None, chat_permissions(TIER_PERMISSION_TEXT)),
+        }
+    }
+
+    pub fn describe(tier: &GroupTier) -> String {
+        format!(
+            "{}: hold {} {} ({})",
+            tier.name,
+            tier.min_balance.unwrap_or(0),
+            tier_token(tier),
+            tier.permission_names().join(", ")
+        )
+    }
+}
+
+fn qualifies(tier: &GroupTier, balances: &Value) -> bool {
+    let balance = balances
+        .get(tier_token(tier))
+        .and_then(Value::as_f64)
+        .unwrap_or(0.0);
+    balance >= tier.min_balance.unwrap_or(0) as f64
+}
+
+/// Key of the tier's token in the balances map
+fn tier_token(tier: &GroupTier) -> String {
+    balance_key(&tier.token_address)
+}
+
+pub fn chat_permissions(permissions: i16) -> ChatPermissions {
+    let mut chat_permissions = ChatPermissions::empty();
+    if permissions & TIER_PERMISSION_TEXT != 0 {
+        chat_permissions |= ChatPermissions::SEND_MESSAGES;
+    }
+    if permissions & TIER_PERMISSION_MEDIA != 0 {
+        chat_permissions |=
+            ChatPermissions::SEND_MEDIA_MESSAGES | ChatPermissions::SEND_OTHER_MESSAGES;
+    }
+    if permissions & TIER_PERMISSION_PREVIEWS != 0 {
+        chat_permissions |= ChatPermissions::ADD_WEB_PAGE_PREVIEWS;
+    }
+    if permissions & TIER_PERMISSION_POLLS != 0 {
+        chat_permissions |= ChatPermissions::SEND_POLLS;
+    }
+    chat_permissions
+}
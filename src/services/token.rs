@@ -0,0 +1,335 @@
+use std::str::FromStr;
+
+use chrono::{Duration, NaiveDateTime, Utc};
+use ckb_jsonrpc_types::{JsonBytes, Script, ScriptHashType};
+use ckb_types::{packed, prelude::*, H256};
+
+use crate::{
+    config,
+    models::{
+        telegram::TelegramGroup,
+        token::{Token, TokenCandidate, TOKEN_TYPE_SPORE, TOKEN_TYPE_XUDT},
+    },
+    repositories::{
+        ckb::{get_cluster_code_hash, get_collection_info, get_xudt_info, search_xudts},
+        metadata::{get_cluster_metadata, get_xudt_metadata},
+        telegram::TelegramDao,
+        token::TokenDao,
+    },
+    serialize::error::AppError,
+};
+
+pub const DEFAULT_TOKEN_REFRESH_HOURS: i64 = 24;
+pub const MAX_TOKENS_PAGE_SIZE: i64 = 100;
+pub const MAX_TOKEN_CANDIDATES: usize = 6;
+
+/// Registry of the xUDT and spore cluster metadata the bot gates on
+#[derive(Clone, Debug)]
+pub struct TokenSrv {
+    token_dao: TokenDao,
+    tele_dao: TelegramDao,
+}
+
+impl TokenSrv {
+    pub fn new(token_dao: TokenDao, tele_dao: TelegramDao) -> Self {
+        TokenSrv {
+            token_dao: token_dao.clone(),
+            tele_dao: tele_dao.clone(),
+        }
+    }
+
+    /// Known token, or its metadata fetched and stored on first use. `ckb` is built in.
+    pub async fn fetch_token(&self, type_hash: String) -> Option<Token> {
+        let type_hash = type_hash.to_lowercase();
+        let now = Utc::now().naive_utc();
+        if type_hash.is_empty() || type_hash == "ckb" {
+            return Some(Token::ckb(now));
+        }
+
+        if let Ok(Some(token)) = self.token_dao.get_token(type_hash.clone()).await {
+            return Some(token);
+        }
+
+        self.refresh_token(type_hash).await
+    }
+
+    pub async fn get_token(&self, type_hash: String) -> Option<Token> {
+        self.token_dao
+            .get_token(type_hash.to_lowercase())
+            .await
+            .unwrap_or(None)
+    }
+
+    /// Re-fetch the token's metadata, keeping the fields an admin overrode. Known tokens fall
+    /// back to reading their metadata from chain when the explorer has none.
+    pub async fn refresh_token(&self, type_hash: String) -> Option<Token> {
+        let type_hash = type_hash.to_lowercase();
+        let now = Utc::now().naive_utc();
+        let token = match fetch_metadata(type_hash.clone(), now).await {
+            Some(token) => token,
+            None => {
+                let known = self.get_token(type_hash).await?;
+                fetch_onchain_metadata(token_script(&known)?, now).await?
+            }
+        };
+        self.store_token(token).await.ok()
+    }
+
+    /// Add a token by its type script, for tokens the explorer does not index (yet)
+    pub async fn register_token(
+        &self,
+        code_hash: String,
+        hash_type: String,
+        args: String,
+    ) -> Result<Token, AppError> {
+        let script = build_script(&code_hash, &hash_type, &args)
+            .ok_or_else(|| AppError::new(400).message("invalid type script"))?;
+        let now = Utc::now().naive_utc();
+        let token = match fetch_metadata(script_hash(&script), now).await {
+            Some(token) => token,
+            None => fetch_onchain_metadata(script, now)
+                .await
+                .ok_or_else(|| AppError::new(404).message("token metadata not found"))?,
+        };
+
+        self.store_token(token).await
+    }
+
+    async fn store_token(&self, token: Token) -> Result<Token, AppError> {
+        self.token_dao
+            .upsert_token(token.clone())
+            .await
+            .map_err(|err| {
+                println!("Store token {} failed: {:?}", token.type_hash, err);
+                AppError::new(500).cause(err).message("store token failed")
+            })
+    }
+
+    /// Refresh every token whose metadata is older than `token_refresh_hours`
+    pub async fn cron_refresh_tokens(&self) {
+        let refresh_hours = config::CONFIG
+            .get::<i64>("token_refresh_hours")
+            .unwrap_or(DEFAULT_TOKEN_REFRESH_HOURS);
+        let refreshed_before = Utc::now().naive_utc() - Duration::hours(refresh_hours);
+        match self.token_dao.get_tokens_to_refresh(refreshed_before).await {
+            Ok(tokens) => {
+                for token in tokens {
+                    if self.refresh_token(token.type_hash.clone()).await.is_none() {
+                        println!("Token {} could not be refreshed", token.type_hash);
+                    }
+                }
+            }
+            Err(err) => {
+                println!("{:?}", err)
+            }
+        }
+    }
+
+    pub async fn search_tokens(
+        &self,
+        search: Option<String>,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Token>, AppError> {
+        let search = search.filter(|search| !search.trim().is_empty());
+        self.token_dao
+            .get_tokens(search, limit.clamp(1, MAX_TOKENS_PAGE_SIZE), offset.max(0))
+            .await
+            .map_err(|e| AppError::new(500).cause(e).message("list tokens failed"))
+    }
+
+    /// Tokens whose symbol or name matches the query, from the registry and the explorer's
+    /// xUDT list, with their holder counts when the explorer knows them
+    pub async fn find_candidates(&self, query: String) -> Vec<TokenCandidate> {
+        let query = query.trim().to_owned();
+        let mut candidates: Vec<TokenCandidate> = self
+            .token_dao
+            .get_tokens(Some(query.clone()), MAX_TOKENS_PAGE_SIZE, 0)
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(TokenCandidate::from)
+            .collect();
+
+        for info in search_xudts(query.clone()).await {
+            let Some(type_hash) = info.type_hash.clone().map(|hash| hash.to_lowercase()) else {
+                continue;
+            };
+            match candidates.iter_mut().find(|c| c.type_hash == type_hash) {
+                Some(candidate) => candidate.holders = info.holders(),
+                None => candidates.push(TokenCandidate {
+                    type_hash,
+                    name: info.full_name.clone(),
+                    symbol: info.symbol.clone(),
+                    decimal: info.decimal.clone(),
+                    holders: info.holders(),
+                }),
+            }
+        }
+
+        TokenCandidate::sort(&mut candidates, &query);
+        candidates.truncate(MAX_TOKEN_CANDIDATES);
+        for candidate in candidates.iter_mut().filter(|c| c.holders.is_none()) {
+            if let Some(info) = get_xudt_info(candidate.type_hash.clone()).await {
+                candidate.holders = info.holders();
+            }
+        }
+
+        candidates
+    }
+
+    pub async fn get_groups_using(&self, type_hash: String) -> Vec<TelegramGroup> {
+        self.tele_dao
+            .get_groups_by_token(type_hash)
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Override the token's name, symbol or decimals; refreshes no longer change them
+    pub async fn override_token(
+        &self,
+        type_hash: String,
+        name: Option<String>,
+        symbol: Option<String>,
+        decimal: Option<u8>,
+    ) -> Result<Token, AppError> {
+        let type_hash = type_hash.to_lowercase();
+        if self.fetch_token(type_hash.clone()).await.is_none() {
+            return Err(AppError::new(404).message("token not found"));
+        }
+
+        self.token_dao
+            .override_token(type_hash, name, symbol, decimal.map(|d| d.to_string()))
+            .await
+            .map_err(|e| AppError::new(500).cause(e).message("override token failed"))?
+            .ok_or_else(|| AppError::new(404).message("token not found"))
+    }
+
+    /// Drop the token's overrides and restore its on-chain metadata
+    pub async fn clear_overrides(&self, type_hash: String) -> Result<Token, AppError> {
+        let type_hash = type_hash.to_lowercase();
+        let cleared = self
+            .token_dao
+            .clear_overrides(type_hash.clone())
+            .await
+            .map_err(|e| {
+                AppError::new(500)
+                    .cause(e)
+                    .message("clear overrides failed")
+            })?;
+        if !cleared {
+            return Err(AppError::new(404).message("token not found"));
+        }
+
+        match self.refresh_token(type_hash.clone()).await {
+            Some(token) => Ok(token),
+            None => self
+                .get_token(type_hash)
+                .await
+                .ok_or_else(|| AppError::new(404).message("token not found")),
+        }
+    }
+}
+
+/// Token metadata from the explorer, as an xUDT or else as a spore cluster
+async fn fetch_metadata(type_hash: String, now: NaiveDateTime) -> Option<Token> {
+    if let Some(info) = get_xudt_info(type_hash.clone()).await {
+        if let Some(ts) = info.type_script {
+            return Some(Token {
+                type_hash,
+                name: info.full_name,
+                symbol: info.symbol,
+                decimal: info.decimal,
+                description: info.description,
+                token_type: TOKEN_TYPE_XUDT,
+                args: ts.args.unwrap_or("".to_owned()),
+                code_hash: ts.code_hash.unwrap_or("".to_owned()),
+                hash_type: ts.hash_type.unwrap_or("".to_owned()),
+                overridden: vec![],
+                refreshed_at: Some(now),
+                created_at: now,
+                updated_at: now,
+            });
+        }
+    }
+
+    // Fallback to collection
+    let info = get_collection_info(type_hash.clone()).await?;
+    let ts = info.type_script;
+    Some(Token {
+        type_hash,
+        name: Some(info.name),
+        symbol: Some(String::new()),
+        decimal: Some(String::new()),
+        description: Some(info.standard),
+        token_type: TOKEN_TYPE_SPORE,
+        args: ts.args,
+        code_hash: ts.code_hash,
+        hash_type: ts.hash_type,
+        overridden: vec![],
+        refreshed_at: Some(now),
+        created_at: now,
+        updated_at: now,
+    })
+}
+
+/// Token metadata read from chain through the CKB RPC, a spore cluster when the script is the
+/// cluster script and an xUDT otherwise
+async fn fetch_onchain_metadata(script: Script, now: NaiveDateTime) -> Option<Token> {
+    let mut token = Token {
+        type_hash: script_hash(&script),
+        name: None,
+        symbol: None,
+        decimal: None,
+        description: None,
+        token_type: TOKEN_TYPE_XUDT,
+        args: format!("0x{}", hex::encode(script.args.as_bytes())),
+        code_hash: format!("{:#x}", script.code_hash),
+        hash_type: serde_json::to_value(&script.hash_type)
+            .ok()?
+            .as_str()?
+            .to_owned(),
+        overridden: vec![],
+        refreshed_at: Some(now),
+        created_at: now,
+        updated_at: now,
+    };
+
+    if token
+        .code_hash
+        .eq_ignore_ascii_case(&get_cluster_code_hash())
+    {
+        let cluster = get_cluster_metadata(script).await?;
+        token.token_type = TOKEN_TYPE_SPORE;
+        token.name = Some(cluster.name);
+        token.symbol = Some(String::new());
+        token.decimal = Some(String::new());
+        token.description = Some(cluster.description);
+    } else {
+        let info = get_xudt_metadata(script).await?;
+        token.name = Some(info.name);
+        token.symbol = Some(info.symbol);
+        token.decimal = Some(info.decimal.to_string());
+    }
+
+    Some(token)
+}
+
+fn token_script(token: &Token) -> Option<Script> {
+    build_script(&token.code_hash, &token.hash_type, &token.args)
+}
+
+fn build_script(code_hash: &str, hash_type: &str, args: &str) -> Option<Script> {
+    Some(Script {
+        code_hash: H256::from_str(code_hash.trim_start_matches("0x")).ok()?,
+        hash_type: serde_json::from_value::<ScriptHashType>(serde_json::json!(hash_type)).ok()?,
+        args: JsonBytes::from_vec(hex::decode(args.trim_start_matches("0x")).ok()?),
+    })
+}
+
+fn script_hash(script: &Script) -> String {
+    let hash: H256 = packed::Script::from(script.clone())
+        .calc_script_hash()
+        .unpack();
+    format!("{:#x}", hash)
+}